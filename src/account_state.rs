@@ -1,9 +1,12 @@
 use std::fmt::{Display, Formatter};
 
-use crate::engine::{ClientId,Amount};
+use serde::{Deserialize, Serialize};
+
+use crate::amount::Amount;
+use crate::engine::ClientId;
 
 /// The state of a client account, `Display`-able as an output CSV line
-#[derive(PartialEq,Debug)]
+#[derive(Clone,PartialEq,Debug,Serialize,Deserialize)]
 pub struct AccountState {
 
     ///client ID
@@ -12,25 +15,54 @@ pub struct AccountState {
     pub available: Amount,
     ///Total disputed funds
     pub held: Amount,
-    ///true IFF a chargeback has been issued on this account
+    ///true IFF this account has been locked (by a chargeback or freeze) and not unlocked since
     pub locked: bool,
 }
 
+impl AccountState {
+
+    ///Total funds: available + held
+    pub fn total(&self) -> Amount {
+        self.available + self.held
+    }
+}
+
 impl Display for AccountState {
 
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
 
-        let total = self.available.0 + self.held.0;
-
         //CSV output line format:
         // client, available, held, total, locked
 
-        write!(f, "{},{:.4},{:.4},{:.4},{}",
-            self.client_id.0, self.available.0, self.held.0, total, self.locked
+        write!(f, "{},{},{},{},{}",
+            self.client_id.0, self.available, self.held, self.total(), self.locked
         )
     }
 }
 
+/// An AccountState as an output record, with the output CSV line's fields: `Serialize`-able (e.g. as JSON)
+#[derive(Clone,PartialEq,Debug,Serialize)]
+pub struct OutputRecord {
+    pub client: u16,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+}
+
+impl From<&AccountState> for OutputRecord {
+
+    fn from(account_state: &AccountState) -> Self {
+        Self {
+            client: account_state.client_id.0,
+            available: account_state.available,
+            held: account_state.held,
+            total: account_state.total(),
+            locked: account_state.locked,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -41,17 +73,25 @@ mod test {
         //success
         {
             let account = AccountState {
-                client_id: ClientId(1), available: Amount(2.00), held: Amount(3.0), locked: false
+                client_id: ClientId(1), available: Amount(2_0000), held: Amount(3_0000), locked: false
             };
             assert_eq!(account.to_string(), "1,2.0000,3.0000,5.0000,false");
         }
 
-        //success with float output truncation
+        //success with all 4 places past the decimal
         {
             let account = AccountState {
-                client_id: ClientId(1), available: Amount(2.12341234), held: Amount(3.0), locked: true
+                client_id: ClientId(1), available: Amount(2_1234), held: Amount(3_0000), locked: true
             };
             assert_eq!(account.to_string(), "1,2.1234,3.0000,5.1234,true");
         }
+
+        //success with negative available funds
+        {
+            let account = AccountState {
+                client_id: ClientId(1), available: Amount(-8_0001), held: Amount(10_0000), locked: false
+            };
+            assert_eq!(account.to_string(), "1,-8.0001,10.0000,1.9999,false");
+        }
     }
 }
\ No newline at end of file
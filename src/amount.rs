@@ -0,0 +1,227 @@
+//! Fixed-point currency amounts
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error as DeError;
+
+
+/// The number of decimal places an Amount can represent
+pub const PRECISION: usize = 4;
+
+/// The number of Amount units per whole currency unit
+const SCALE: i64 = 10_000;
+
+/// A deposit, withdrawal, or balance amount:
+/// an exact fixed-point decimal with 4 places past the decimal,
+/// stored as an integral number of ten-thousandths
+/// (e.g. `Amount(1_5000)` is 1.5)
+#[derive(Copy,Clone,Eq,PartialEq,Ord,PartialOrd,Hash,Default,Debug)]
+pub struct Amount(pub i64);
+
+/// An Amount string that couldn't be parsed
+#[derive(PartialEq,Debug)]
+pub struct ParseAmountError(String);
+
+impl Display for ParseAmountError {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid amount: {:?}", self.0)
+    }
+}
+
+impl Error for ParseAmountError {}
+
+impl FromStr for Amount {
+    type Err = ParseAmountError;
+
+    /// Parses a decimal string like "-12.3456" exactly:
+    /// more than 4 fractional digits is an error, not a rounding
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        let err = || ParseAmountError(s.to_string());
+
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+
+        let (whole, fraction) = match digits.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (digits, ""),
+        };
+
+        //at least one digit is required, and only digits are allowed
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(err());
+        }
+        if !whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(err());
+        }
+        if fraction.len() > PRECISION {
+            return Err(err());
+        }
+
+        let whole = if whole.is_empty() { 0 } else { whole.parse::<i64>().map_err(|_| err())? };
+
+        //right-pad the fraction to exactly PRECISION digits
+        let fraction = format!("{:0<width$}", fraction, width = PRECISION).parse::<i64>().map_err(|_| err())?;
+
+        let units = whole.checked_mul(SCALE)
+            .and_then(|units| units.checked_add(fraction))
+            .ok_or_else(err)?;
+
+        Ok(Amount(if negative { -units } else { units }))
+    }
+}
+
+impl Amount {
+
+    /// Adds `rhs`, or returns None on overflow
+    pub fn checked_add(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_add(rhs.0).map(Amount)
+    }
+
+    /// Subtracts `rhs`, or returns None on overflow
+    pub fn checked_sub(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_sub(rhs.0).map(Amount)
+    }
+}
+
+impl Display for Amount {
+
+    /// Always writes exactly 4 places past the decimal
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let units = self.0.unsigned_abs();
+        let scale = SCALE as u64;
+
+        write!(f, "{}{}.{:0width$}", sign, units / scale, units % scale, width = PRECISION)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+
+    /// Deserializes from the amount's text, so no float conversion is involved
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(DeError::custom)
+    }
+}
+
+impl Serialize for Amount {
+
+    /// Serializes as the amount's text, e.g. "1.5000"
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Add for Amount {
+    type Output = Amount;
+
+    fn add(self, rhs: Amount) -> Amount {
+        Amount(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Amount {
+    type Output = Amount;
+
+    fn sub(self, rhs: Amount) -> Amount {
+        Amount(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Amount {
+    type Output = Amount;
+
+    fn neg(self) -> Amount {
+        Amount(-self.0)
+    }
+}
+
+impl AddAssign for Amount {
+
+    fn add_assign(&mut self, rhs: Amount) {
+        self.0 += rhs.0;
+    }
+}
+
+impl SubAssign for Amount {
+
+    fn sub_assign(&mut self, rhs: Amount) {
+        self.0 -= rhs.0;
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_str_test() {
+
+        //success
+        assert_eq!("3".parse(), Ok(Amount(3_0000)));
+        assert_eq!("3.0".parse(), Ok(Amount(3_0000)));
+        assert_eq!("3.1234".parse(), Ok(Amount(3_1234)));
+        assert_eq!("0.0001".parse(), Ok(Amount(1)));
+        assert_eq!(".5".parse(), Ok(Amount(5000)));
+        assert_eq!("5.".parse(), Ok(Amount(5_0000)));
+        assert_eq!("+5".parse(), Ok(Amount(5_0000)));
+        assert_eq!("-2.25".parse(), Ok(Amount(-2_2500)));
+
+        //failure: too many fractional digits
+        assert!("3.12345".parse::<Amount>().is_err());
+
+        //failure: not a number
+        assert!("".parse::<Amount>().is_err());
+        assert!(".".parse::<Amount>().is_err());
+        assert!("-".parse::<Amount>().is_err());
+        assert!("1.2.3".parse::<Amount>().is_err());
+        assert!("1e5".parse::<Amount>().is_err());
+        assert!("--1".parse::<Amount>().is_err());
+
+        //failure: overflow
+        assert!("9223372036854775807".parse::<Amount>().is_err());
+    }
+
+    #[test]
+    fn display_test() {
+
+        assert_eq!(Amount(0).to_string(), "0.0000");
+        assert_eq!(Amount(1).to_string(), "0.0001");
+        assert_eq!(Amount(2_5000).to_string(), "2.5000");
+        assert_eq!(Amount(-5000).to_string(), "-0.5000");
+        assert_eq!(Amount(-12_3456).to_string(), "-12.3456");
+        assert_eq!(Amount(i64::MIN).to_string(), "-922337203685477.5808");
+    }
+
+    #[test]
+    fn arithmetic_test() {
+
+        //exact: 0.1 added 10,000 times is exactly 1,000
+        let mut total = Amount(0);
+        for _ in 0..10_000 {
+            total += "0.1".parse().unwrap();
+        }
+        assert_eq!(total, Amount(1000_0000));
+
+        assert_eq!(Amount(1_0000) + Amount(2_5000), Amount(3_5000));
+        assert_eq!(Amount(1_0000) - Amount(2_5000), Amount(-1_5000));
+        assert_eq!(-Amount(1_0000), Amount(-1_0000));
+
+        //checked
+        assert_eq!(Amount(1_0000).checked_add(Amount(2_5000)), Some(Amount(3_5000)));
+        assert_eq!(Amount(1_0000).checked_sub(Amount(2_5000)), Some(Amount(-1_5000)));
+        assert_eq!(Amount(i64::MAX).checked_add(Amount(1)), None);
+        assert_eq!(Amount(i64::MIN).checked_sub(Amount(1)), None);
+    }
+}
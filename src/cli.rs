@@ -0,0 +1,521 @@
+//! Command-line argument handling
+
+use rust2021_6_21::engine::{AsOf,EngineConfig};
+use rust2021_6_21::input::{InputFormat,ParsePolicy};
+use rust2021_6_21::output::{CsvOptions,OutputFormat};
+use rust2021_6_21::timestamp::Timestamp;
+
+/// The usage message for invalid arguments
+pub const USAGE: &str = "Usage: rust2021_6_21 <input.csv|input.jsonl|glob>... [--input-format <csv|jsonl>] [--format <csv|json|jsonl|table>] \
+[--output <output>] [--csv-header <plain|spaced|none>] [--csv-delimiter <char|tab>] [--csv-quote <necessary|always|never|non-numeric>] \
+[--rejections <rejections.csv>] [--admin-actions <admin_actions.csv>] [--ledger <ledger.csv>] [--open-disputes <disputes.csv>] \
+[--parse-policy <strict|lenient|threshold:N>] [--no-redispute] [--dispute-window <window>] [--chargeback-deadline <window>] [--threads <N>] \
+[--as-of-line <line> | --as-of-tx <tx>] [--cutoff <timestamp>] \
+[--load-snapshot <snapshot.json>] [--save-snapshot <snapshot.json>] [--journal <journal>]
+       rust2021_6_21 <serve|http> <address:port> [--no-redispute] [--dispute-window <window>] [--chargeback-deadline <window>] [--load-snapshot <snapshot.json>] [--journal <journal>]";
+
+/// The options specified by the command-line arguments
+#[derive(PartialEq,Debug)]
+pub struct Options {
+    ///the input file paths or glob patterns, processed in order as one input (empty in serve mode)
+    pub input_paths: Vec<String>,
+    ///the input file format: if not specified, it's implied by each input path's extension
+    pub input_format: Option<InputFormat>,
+    ///the account state output format
+    pub output_format: OutputFormat,
+    ///CSV account state output options
+    pub csv_options: CsvOptions,
+    ///if present: where to write the account state output, instead of stdout
+    pub output_path: Option<String>,
+    ///if present: serve mode, listening on this TCP address instead of processing an input file
+    pub serve_address: Option<String>,
+    ///in serve mode: whether to serve the HTTP/JSON API, rather than the line protocol
+    pub http: bool,
+    ///if present: where to write the rejected transactions CSV
+    pub rejections_path: Option<String>,
+    ///if present: where to write the applied admin actions CSV
+    pub admin_actions_path: Option<String>,
+    ///if present: where to write the audit ledger CSV (a line for each applied transaction)
+    pub ledger_path: Option<String>,
+    ///if present: where to write the open disputes CSV (the disputes still open after processing)
+    pub open_disputes_path: Option<String>,
+    ///how invalid input lines are handled
+    pub parse_policy: ParsePolicy,
+    ///transaction processing options
+    pub engine_config: EngineConfig,
+    ///if present: the engine state snapshot to continue from
+    pub load_snapshot_path: Option<String>,
+    ///if present: where to write the engine state snapshot after processing
+    pub save_snapshot_path: Option<String>,
+    ///if present: the write-ahead journal to replay, then append to
+    pub journal_path: Option<String>,
+    ///if present: the point in the input to process up to (and including), for point-in-time account states
+    pub as_of: Option<AsOf>,
+    ///if present: records timestamped after this are skipped, for account states as of a cutoff time
+    pub cutoff: Option<Timestamp>,
+    ///the number of worker threads client accounts are sharded across (1: no worker threads)
+    pub threads: usize,
+}
+
+impl Default for Options {
+
+    fn default() -> Self {
+        Self {
+            input_paths: Vec::new(),
+            input_format: None,
+            output_format: OutputFormat::default(),
+            csv_options: CsvOptions::default(),
+            output_path: None,
+            serve_address: None,
+            http: false,
+            rejections_path: None,
+            admin_actions_path: None,
+            ledger_path: None,
+            open_disputes_path: None,
+            parse_policy: ParsePolicy::default(),
+            engine_config: EngineConfig::default(),
+            load_snapshot_path: None,
+            save_snapshot_path: None,
+            journal_path: None,
+            as_of: None,
+            cutoff: None,
+            threads: 1,
+        }
+    }
+}
+
+impl Options {
+
+    /// Parses the command-line arguments (excluding the program name)
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+
+        let mut args = args.into_iter().peekable();
+
+        let mut options = Options::default();
+
+        if let Some(mode) = args.next_if(|arg| arg == "serve" || arg == "http") {
+            options.http = mode == "http";
+            options.serve_address = Some(args.next().ok_or_else(|| format!("{} requires an address", mode))?);
+        }
+
+        while let Some(arg) = args.next() {
+
+            match arg.as_str() {
+                "--input-format" => {
+                    options.input_format = Some(args.next().ok_or("--input-format requires a format")?.parse()?);
+                },
+                "--format" => {
+                    options.output_format = args.next().ok_or("--format requires a format")?.parse()?;
+                },
+                "--output" => {
+                    options.output_path = Some(args.next().ok_or("--output requires a path")?);
+                },
+                "--csv-header" => {
+                    options.csv_options.header = args.next().ok_or("--csv-header requires a header style")?.parse()?;
+                },
+                "--csv-delimiter" => {
+                    options.csv_options.delimiter = parse_delimiter(&args.next().ok_or("--csv-delimiter requires a delimiter")?)?;
+                },
+                "--csv-quote" => {
+                    options.csv_options.quoting = args.next().ok_or("--csv-quote requires a quoting style")?.parse()?;
+                },
+                "--rejections" => {
+                    options.rejections_path = Some(args.next().ok_or("--rejections requires a path")?);
+                },
+                "--admin-actions" => {
+                    options.admin_actions_path = Some(args.next().ok_or("--admin-actions requires a path")?);
+                },
+                "--ledger" => {
+                    options.ledger_path = Some(args.next().ok_or("--ledger requires a path")?);
+                },
+                "--open-disputes" => {
+                    options.open_disputes_path = Some(args.next().ok_or("--open-disputes requires a path")?);
+                },
+                "--parse-policy" => {
+                    options.parse_policy = args.next().ok_or("--parse-policy requires a policy")?.parse()?;
+                },
+                "--no-redispute" => {
+                    options.engine_config.allow_redispute = false;
+                },
+                "--dispute-window" => {
+                    options.engine_config.dispute_window = Some(args.next().ok_or("--dispute-window requires a window")?.parse()?);
+                },
+                "--chargeback-deadline" => {
+                    options.engine_config.chargeback_deadline = Some(args.next().ok_or("--chargeback-deadline requires a window")?.parse()?);
+                },
+                "--load-snapshot" => {
+                    options.load_snapshot_path = Some(args.next().ok_or("--load-snapshot requires a path")?);
+                },
+                "--save-snapshot" => {
+                    options.save_snapshot_path = Some(args.next().ok_or("--save-snapshot requires a path")?);
+                },
+                "--journal" => {
+                    options.journal_path = Some(args.next().ok_or("--journal requires a path")?);
+                },
+                "--as-of-line" => {
+                    let line = args.next().ok_or("--as-of-line requires a line number")?.parse()
+                        .map_err(|_| "--as-of-line requires a valid line number")?;
+                    if options.as_of.replace(AsOf::Line(line)).is_some() {
+                        return Err("only one of --as-of-line or --as-of-tx can be specified".to_string());
+                    }
+                },
+                "--as-of-tx" => {
+                    let tx = args.next().ok_or("--as-of-tx requires a transaction ID")?.parse()
+                        .map_err(|_| "--as-of-tx requires a valid transaction ID")?;
+                    if options.as_of.replace(AsOf::Tx(tx)).is_some() {
+                        return Err("only one of --as-of-line or --as-of-tx can be specified".to_string());
+                    }
+                },
+                "--cutoff" => {
+                    options.cutoff = Some(args.next().ok_or("--cutoff requires a timestamp")?.parse().map_err(|e| format!("--cutoff: {}", e))?);
+                },
+                "--threads" => {
+                    options.threads = args.next().ok_or("--threads requires a thread count")?.parse()
+                        .ok().filter(|&threads| threads > 0).ok_or("--threads requires a positive thread count")?;
+                },
+                flag if flag.starts_with("--") => {
+                    return Err(format!("unknown option: {}", flag));
+                },
+                _ => {
+                    options.input_paths.push(arg);
+                },
+            }
+        }
+
+        if options.serve_address.is_some() {
+            if !options.input_paths.is_empty() {
+                return Err("serve mode doesn't take an input path".to_string());
+            }
+            if options.rejections_path.is_some() || options.admin_actions_path.is_some() || options.save_snapshot_path.is_some()
+                || options.output_path.is_some() || options.ledger_path.is_some() || options.open_disputes_path.is_some()
+                || options.as_of.is_some() || options.cutoff.is_some() {
+                return Err("serve mode doesn't support --rejections, --admin-actions, --save-snapshot, --output, --ledger, --open-disputes, \
+--as-of options, or --cutoff".to_string());
+            }
+        }
+        else if options.input_paths.is_empty() {
+            return Err("Specify input path".to_string());
+        }
+
+        //admin actions are reported as each one is applied, in input order
+        if options.admin_actions_path.is_some() && options.threads > 1 {
+            return Err("--admin-actions requires sequential processing (no --threads)".to_string());
+        }
+
+        //each record's outcome is journaled after it's applied, in input order
+        if options.journal_path.is_some() && options.threads > 1 {
+            return Err("--journal requires sequential processing (no --threads)".to_string());
+        }
+
+        //a point-in-time query skips records, so its state isn't persisted
+        if options.save_snapshot_path.is_some() || options.journal_path.is_some() {
+            if options.as_of.is_some() {
+                return Err("--as-of options can't be combined with --save-snapshot or --journal".to_string());
+            }
+            if options.cutoff.is_some() {
+                return Err("--cutoff can't be combined with --save-snapshot or --journal".to_string());
+            }
+        }
+
+        //the ledger is written in input order, as each transaction is applied
+        if options.ledger_path.is_some() && options.threads > 1 {
+            return Err("--ledger requires sequential processing (no --threads)".to_string());
+        }
+
+        Ok(options)
+    }
+}
+
+/// Expands glob patterns (paths containing `*`, `?`, or `[`) into their matching file paths, in alphabetical order.
+/// Other paths are kept as they are, and the order of the arguments is kept.
+pub fn expand_globs(paths: &[String]) -> Result<Vec<String>, String> {
+
+    let mut expanded = Vec::new();
+
+    for path in paths {
+
+        if !path.contains(['*', '?', '[']) {
+            expanded.push(path.clone());
+            continue;
+        }
+
+        let matches = glob::glob(path).map_err(|e| format!("invalid glob pattern {}: {}", path, e))?
+            .map(|entry| entry.map(|path| path.to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        if matches.is_empty() {
+            return Err(format!("no input files match {}", path));
+        }
+        expanded.extend(matches);
+    }
+
+    Ok(expanded)
+}
+
+/// Parses a CSV delimiter: a single ASCII character (other than a quote or line break), or `tab`
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+
+    match s.as_bytes() {
+        b"tab" => Ok(b'\t'),
+        &[delimiter] if delimiter.is_ascii() && !matches!(delimiter, b'"' | b'\n' | b'\r') => Ok(delimiter),
+        _ => Err(format!("invalid CSV delimiter: {}", s)),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use rust2021_6_21::engine::Window;
+    use rust2021_6_21::output::{HeaderStyle,Quoting};
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn parse_test() {
+
+        //input path only
+        assert_eq!(parse(&["in.csv"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            ..Options::default()
+        }));
+
+        //rejections path, before or after the input path
+        assert_eq!(parse(&["in.csv", "--rejections", "rej.csv"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            rejections_path: Some("rej.csv".to_string()),
+            ..Options::default()
+        }));
+        assert_eq!(parse(&["--rejections", "rej.csv", "in.csv"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            rejections_path: Some("rej.csv".to_string()),
+            ..Options::default()
+        }));
+
+        //admin actions path
+        assert_eq!(parse(&["in.csv", "--admin-actions", "admin.csv"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            admin_actions_path: Some("admin.csv".to_string()),
+            ..Options::default()
+        }));
+
+        //input format
+        assert_eq!(parse(&["in.txt", "--input-format", "jsonl"]), Ok(Options {
+            input_paths: vec!["in.txt".to_string()],
+            input_format: Some(InputFormat::Jsonl),
+            ..Options::default()
+        }));
+
+        //failure: invalid input format
+        assert!(parse(&["in.txt", "--input-format", "xml"]).is_err());
+
+        //output format
+        assert_eq!(parse(&["in.csv", "--format", "table"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            output_format: OutputFormat::Table,
+            ..Options::default()
+        }));
+
+        //failure: invalid output format
+        assert!(parse(&["in.csv", "--format", "xml"]).is_err());
+
+        //output path and CSV options
+        assert_eq!(parse(&["in.csv", "--output", "out.tsv", "--csv-header", "none", "--csv-delimiter", "tab", "--csv-quote", "always"]),
+            Ok(Options {
+                input_paths: vec!["in.csv".to_string()],
+                output_path: Some("out.tsv".to_string()),
+                csv_options: CsvOptions { header: HeaderStyle::None, delimiter: b'\t', quoting: Quoting::Always },
+                ..Options::default()
+            }));
+        assert_eq!(parse(&["in.csv", "--csv-delimiter", ";", "--csv-header", "spaced"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            csv_options: CsvOptions { header: HeaderStyle::Spaced, delimiter: b';', ..CsvOptions::default() },
+            ..Options::default()
+        }));
+
+        //ledger path
+        assert_eq!(parse(&["in.csv", "--ledger", "ledger.csv"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            ledger_path: Some("ledger.csv".to_string()),
+            ..Options::default()
+        }));
+
+        //open disputes path
+        assert_eq!(parse(&["in.csv", "--open-disputes", "disputes.csv", "--threads", "2"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            open_disputes_path: Some("disputes.csv".to_string()),
+            threads: 2,
+            ..Options::default()
+        }));
+
+        //point in time
+        assert_eq!(parse(&["in.csv", "--as-of-line", "42"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            as_of: Some(AsOf::Line(42)),
+            ..Options::default()
+        }));
+        assert_eq!(parse(&["in.csv", "--as-of-tx", "7"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            as_of: Some(AsOf::Tx(7)),
+            ..Options::default()
+        }));
+
+        //cutoff time
+        assert_eq!(parse(&["in.csv", "--cutoff", "1970-01-01T00:00:01Z"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            cutoff: Some(Timestamp(1_000_000_000)),
+            ..Options::default()
+        }));
+
+        //failure: invalid points in time
+        assert!(parse(&["in.csv", "--cutoff", "noon"]).is_err());
+        assert!(parse(&["in.csv", "--as-of-line", "-1"]).is_err());
+        assert!(parse(&["in.csv", "--as-of-tx", "x"]).is_err());
+        assert!(parse(&["in.csv", "--as-of-line", "4", "--as-of-tx", "7"]).is_err());
+        assert!(parse(&["serve", "127.0.0.1:7878", "--as-of-tx", "7"]).is_err());
+
+        //failure: a point in time with persisted state
+        assert!(parse(&["in.csv", "--as-of-line", "4", "--save-snapshot", "s.json"]).is_err());
+        assert!(parse(&["in.csv", "--as-of-tx", "7", "--journal", "j.bin"]).is_err());
+        assert!(parse(&["in.csv", "--cutoff", "150", "--save-snapshot", "s.json"]).is_err());
+        assert!(parse(&["in.csv", "--cutoff", "150", "--journal", "j.bin"]).is_err());
+
+        //failure: a ledger with worker threads
+        assert!(parse(&["in.csv", "--ledger", "ledger.csv", "--threads", "2"]).is_err());
+
+        //failure: invalid CSV options
+        assert!(parse(&["in.csv", "--csv-delimiter", ";;"]).is_err());
+        assert!(parse(&["in.csv", "--csv-delimiter", "\""]).is_err());
+        assert!(parse(&["in.csv", "--csv-delimiter", "é"]).is_err());
+        assert!(parse(&["in.csv", "--csv-header", "fancy"]).is_err());
+        assert!(parse(&["in.csv", "--csv-quote", "sometimes"]).is_err());
+
+        //parse policy
+        assert_eq!(parse(&["in.csv", "--parse-policy", "threshold:3"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            parse_policy: ParsePolicy::Threshold(3),
+            ..Options::default()
+        }));
+
+        //engine config
+        assert_eq!(parse(&["in.csv", "--no-redispute"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            engine_config: EngineConfig { allow_redispute: false, ..EngineConfig::default() },
+            ..Options::default()
+        }));
+        assert_eq!(parse(&["serve", "127.0.0.1:7878", "--dispute-window", "120d", "--chargeback-deadline", "1000tx"]), Ok(Options {
+            serve_address: Some("127.0.0.1:7878".to_string()),
+            engine_config: EngineConfig {
+                dispute_window: Some(Window::Time(Duration::from_secs(120 * 24 * 60 * 60))),
+                chargeback_deadline: Some(Window::Transactions(1000)),
+                ..EngineConfig::default()
+            },
+            ..Options::default()
+        }));
+
+        //failure: invalid windows
+        assert!(parse(&["in.csv", "--dispute-window", "soon"]).is_err());
+        assert!(parse(&["in.csv", "--chargeback-deadline"]).is_err());
+
+        //snapshot paths
+        assert_eq!(parse(&["in.csv", "--load-snapshot", "day1.json", "--save-snapshot", "day2.json"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            load_snapshot_path: Some("day1.json".to_string()),
+            save_snapshot_path: Some("day2.json".to_string()),
+            ..Options::default()
+        }));
+
+        //journal path
+        assert_eq!(parse(&["in.csv", "--journal", "journal.bin"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            journal_path: Some("journal.bin".to_string()),
+            ..Options::default()
+        }));
+
+        //thread count
+        assert_eq!(parse(&["in.csv", "--threads", "4"]), Ok(Options {
+            input_paths: vec!["in.csv".to_string()],
+            threads: 4,
+            ..Options::default()
+        }));
+
+        //failure: an admin actions report with worker threads
+        assert!(parse(&["in.csv", "--admin-actions", "admin.csv", "--threads", "2"]).is_err());
+
+        //failure: a journal with worker threads
+        assert!(parse(&["in.csv", "--journal", "journal.bin", "--threads", "2"]).is_err());
+        //serve mode
+        assert_eq!(parse(&["serve", "127.0.0.1:7878", "--journal", "journal.bin"]), Ok(Options {
+            serve_address: Some("127.0.0.1:7878".to_string()),
+            journal_path: Some("journal.bin".to_string()),
+            ..Options::default()
+        }));
+
+        assert_eq!(parse(&["http", "127.0.0.1:8080"]), Ok(Options {
+            serve_address: Some("127.0.0.1:8080".to_string()),
+            http: true,
+            ..Options::default()
+        }));
+
+        //failure: invalid serve mode options
+        assert!(parse(&["serve"]).is_err());
+        assert!(parse(&["http"]).is_err());
+        assert!(parse(&["serve", "127.0.0.1:7878", "in.csv"]).is_err());
+        assert!(parse(&["serve", "127.0.0.1:7878", "--rejections", "rej.csv"]).is_err());
+        assert!(parse(&["serve", "127.0.0.1:7878", "--admin-actions", "admin.csv"]).is_err());
+        assert!(parse(&["serve", "127.0.0.1:7878", "--output", "out.csv"]).is_err());
+        assert!(parse(&["serve", "127.0.0.1:7878", "--ledger", "ledger.csv"]).is_err());
+        assert!(parse(&["serve", "127.0.0.1:7878", "--open-disputes", "disputes.csv"]).is_err());
+
+        //failure: invalid thread count
+        assert!(parse(&["in.csv", "--threads", "0"]).is_err());
+        assert!(parse(&["in.csv", "--threads", "many"]).is_err());
+
+        //failure: invalid parse policy
+        assert!(parse(&["in.csv", "--parse-policy", "relaxed"]).is_err());
+
+        //failure: no input path
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--rejections", "rej.csv"]).is_err());
+
+        //failure: missing option value
+        assert!(parse(&["in.csv", "--rejections"]).is_err());
+
+        //failure: unknown option
+        assert!(parse(&["in.csv", "--no-such-option"]).is_err());
+
+        //multiple input paths, in order
+        assert_eq!(parse(&["in2.csv", "--threads", "2", "in1.csv", "in*.csv"]), Ok(Options {
+            input_paths: vec!["in2.csv".to_string(), "in1.csv".to_string(), "in*.csv".to_string()],
+            threads: 2,
+            ..Options::default()
+        }));
+    }
+
+    #[test]
+    fn expand_globs_test() {
+
+        let expand = |paths: &[&str]| expand_globs(&paths.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+
+        //success: patterns are expanded in place, in alphabetical order
+        assert_eq!(expand(&["tests/general_test.jsonl", "tests/snapshot_day?_test.csv", "tests/basic_test.csv"]), Ok(vec![
+            "tests/general_test.jsonl".to_string(),
+            "tests/snapshot_day1_test.csv".to_string(),
+            "tests/snapshot_day2_test.csv".to_string(),
+            "tests/basic_test.csv".to_string(),
+        ]));
+
+        //success: other paths are kept, even if they don't exist
+        assert_eq!(expand(&["no_such_file.csv"]), Ok(vec!["no_such_file.csv".to_string()]));
+
+        //failure: no matches
+        assert!(expand(&["tests/no_such_*.csv"]).is_err());
+
+        //failure: invalid pattern
+        assert!(expand(&["tests/[.csv"]).is_err());
+    }
+}
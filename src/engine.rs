@@ -1,76 +1,410 @@
 //! The transaction processing engine
 
 use std::collections::{BTreeMap,HashMap,HashSet};
-use std::error::Error;
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
 
-use crate::input::InputRecord;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::amount::Amount;
+use crate::input::{InputError,InputErrorKind,InputRecord};
 use crate::account_state::AccountState;
+use crate::timestamp::Timestamp;
 
 
 /// A client ID
-#[derive(Copy,Clone,Eq,PartialEq,Ord,PartialOrd,Debug)]
+#[derive(Copy,Clone,Eq,PartialEq,Ord,PartialOrd,Debug,Serialize,Deserialize)]
 pub struct ClientId(pub u16);
 
 /// A globally-unique transaction ID
-#[derive(Copy,Clone,Eq,PartialEq,Hash,Debug)]
-struct TxId(u32);
-
-/// A deposit or withdrawal amount; expected precision is 4 places past the decimal
-#[derive(Copy,Clone,PartialEq,Debug)]
-pub struct Amount(pub f32);
+#[derive(Copy,Clone,Eq,PartialEq,Hash,Debug,Serialize,Deserialize)]
+pub(crate) struct TxId(u32);
 
 /// A transaction that applies to a client account
 #[derive(PartialEq,Debug)]
-enum Transaction {
+pub(crate) enum Transaction {
     Deposit(TxId, Amount),
     Withdrawal(TxId, Amount),
     Dispute(TxId),
     Resolve(TxId),
     Chargeback(TxId),
+    //admin actions: the transaction ID identifies the action itself
+    Unlock(TxId),
+    Freeze(TxId),
+}
+
+/// Why a transaction record was rejected, i.e. ignored without any effect
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub enum RejectionReason {
+    /// The record isn't a valid transaction, for the specified reason
+    InvalidRecord(InputErrorKind),
+    /// The client account hasn't been opened by a deposit yet
+    AccountNotOpen,
+    /// The client account is locked
+    AccountLocked,
+    /// A withdrawal exceeds the client's available funds
+    InsufficientFunds,
+    /// The referenced transaction doesn't exist on this client account
+    UnknownTx,
+    /// The referenced transaction isn't disputed
+    NotDisputed,
+    /// Applying the transaction would overflow the client's available, held, or total funds
+    Overflow,
+    /// A dispute references a transaction that's already disputed
+    AlreadyDisputed,
+    /// A dispute references a transaction whose dispute was resolved, and re-disputes aren't allowed
+    AlreadyResolved,
+    /// A dispute references a transaction that's been charged back
+    AlreadyChargedBack,
+    /// An unlock targets an account that isn't locked
+    NotLocked,
+    /// A deposit, withdrawal, or admin action reuses a transaction ID that has already been seen (on any client account)
+    DuplicateTx,
+    /// The record's timestamp is earlier than the client's last applied transaction's
+    OutOfOrder,
+    /// A dispute references a transaction that's older than the dispute window
+    DisputeWindowExpired,
+    /// A chargeback references a dispute that's been open past the chargeback deadline
+    ChargebackDeadlinePassed,
+}
+
+impl Display for RejectionReason {
+
+    ///Writes the reason's name (for an invalid record, the name of its input error kind)
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+
+        match self {
+            RejectionReason::InvalidRecord(kind) => write!(f, "{:?}", kind),
+            reason => write!(f, "{:?}", reason),
+        }
+    }
+}
+
+impl Serialize for RejectionReason {
+
+    ///Serializes the reason as its `Display` name
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A rejected transaction record, `Serialize`-able as a rejection report CSV line
+#[derive(Clone,PartialEq,Debug,Serialize)]
+pub struct Rejection {
+    ///the record's line number in its input
+    pub line: u64,
+    pub client: u16,
+    pub tx: u32,
+    pub reason: RejectionReason,
+}
+
+impl Rejection {
+
+    pub(crate) fn new(record: &InputRecord, reason: RejectionReason) -> Self {
+        Self { line: record.line, client: record.client, tx: record.tx, reason }
+    }
+}
+
+impl Display for Rejection {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: client {}, tx {}: {}", self.line, self.client, self.tx, self.reason)
+    }
 }
 
 /// Parses an InputRecord into a client ID + Transaction pair
-fn parse_record(record: &InputRecord) -> Result<(ClientId, Transaction), Box<dyn Error>> {
+fn parse_record(record: &InputRecord) -> Result<(ClientId, Transaction), InputError> {
 
-    match record {
+    let client_id = ClientId(record.client);
+    let tx_id = TxId(record.tx);
 
-        InputRecord{r#type, client,tx, amount: Some(amount)} => {
+    let line = record.line;
 
-            match r#type.as_str() {
-                "deposit"    => Ok((ClientId(*client), Transaction::Deposit(TxId(*tx), Amount(*amount)))),
-                "withdrawal" => Ok((ClientId(*client), Transaction::Withdrawal(TxId(*tx), Amount(*amount)))),
-                _ => Err("invalid input record".into())
+    match (record.r#type.as_str(), record.amount) {
 
-            }
+        //a negative withdrawal would create funds, and a negative deposit would remove them
+        ("deposit", Some(amount)) | ("withdrawal", Some(amount)) if amount <= Amount(0) => {
+            Err(InputError::NonPositiveAmount { line, raw: record.to_string() })
+        },
+        ("deposit",    Some(amount)) => Ok((client_id, Transaction::Deposit(tx_id, amount))),
+        ("withdrawal", Some(amount)) => Ok((client_id, Transaction::Withdrawal(tx_id, amount))),
+        ("dispute",    None) => Ok((client_id, Transaction::Dispute(tx_id))),
+        ("resolve",    None) => Ok((client_id, Transaction::Resolve(tx_id))),
+        ("chargeback", None) => Ok((client_id, Transaction::Chargeback(tx_id))),
+        ("unlock",     None) => Ok((client_id, Transaction::Unlock(tx_id))),
+        ("freeze",     None) => Ok((client_id, Transaction::Freeze(tx_id))),
+
+        ("deposit", None) | ("withdrawal", None) => {
+            Err(InputError::MissingAmount { line, raw: record.to_string() })
         },
+        ("dispute", Some(_)) | ("resolve", Some(_)) | ("chargeback", Some(_)) |
+        ("unlock", Some(_)) | ("freeze", Some(_)) => {
+            Err(InputError::UnexpectedAmount { line, raw: record.to_string() })
+        },
+        _ => Err(InputError::UnknownType { line, raw: record.to_string() }),
+    }
+}
 
-        InputRecord{r#type, client,tx, amount: None} => {
+/// Adds `available` and `held` to an account's funds,
+/// or leaves them unchanged if its available, held, or total funds would overflow
+fn adjust_funds(account_state: &mut AccountState, available: Amount, held: Amount) -> Result<(), RejectionReason> {
 
-            match r#type.as_str() {
-                "dispute"    => Ok((ClientId(*client), Transaction::Dispute(TxId(*tx)))),
-                "resolve"    => Ok((ClientId(*client), Transaction::Resolve(TxId(*tx)))),
-                "chargeback" => Ok((ClientId(*client), Transaction::Chargeback(TxId(*tx)))),
-                _ => Err("invalid input record".into())
-            }
+    let available = account_state.available.checked_add(available);
+    let held = account_state.held.checked_add(held);
+
+    match (available, held) {
+        (Some(available), Some(held)) if available.checked_add(held).is_some() => {
+            account_state.available = available;
+            account_state.held = held;
+            Ok(())
         },
+        _ => Err(RejectionReason::Overflow),
+    }
+}
+
+/// A transaction that can be disputed, with its amount
+#[derive(Copy,Clone,PartialEq,Debug,Serialize,Deserialize)]
+enum Disputable {
+    Deposit(Amount),
+    Withdrawal(Amount),
+}
+
+impl Disputable {
+
+    /// The transaction type ("deposit" or "withdrawal"), and its amount
+    fn type_and_amount(self) -> (&'static str, Amount) {
+
+        match self {
+            Disputable::Deposit(amount) => ("deposit", amount),
+            Disputable::Withdrawal(amount) => ("withdrawal", amount),
+        }
+    }
+}
+
+/// A disputable transaction's place in the dispute lifecycle:
+/// Settled -> Disputed -> Resolved or ChargedBack.
+/// A Resolved transaction can be Disputed again, if allowed by the EngineConfig.
+#[derive(Copy,Clone,Eq,PartialEq,Debug,Serialize,Deserialize)]
+pub enum DisputeState {
+    Settled,
+    Disputed,
+    Resolved,
+    ChargedBack,
+}
+
+impl DisputeState {
+
+    /// The state after a dispute, or the reason the transition is invalid
+    fn dispute(self, config: &EngineConfig) -> Result<Self, RejectionReason> {
+
+        match self {
+            DisputeState::Settled => Ok(DisputeState::Disputed),
+            DisputeState::Resolved if config.allow_redispute => Ok(DisputeState::Disputed),
+            DisputeState::Resolved => Err(RejectionReason::AlreadyResolved),
+            DisputeState::Disputed => Err(RejectionReason::AlreadyDisputed),
+            DisputeState::ChargedBack => Err(RejectionReason::AlreadyChargedBack),
+        }
+    }
+
+    /// The state after a resolve, or the reason the transition is invalid
+    fn resolve(self) -> Result<Self, RejectionReason> {
+
+        match self {
+            DisputeState::Disputed => Ok(DisputeState::Resolved),
+            _ => Err(RejectionReason::NotDisputed),
+        }
+    }
+
+    /// The state after a chargeback, or the reason the transition is invalid
+    fn chargeback(self) -> Result<Self, RejectionReason> {
+
+        match self {
+            DisputeState::Disputed => Ok(DisputeState::ChargedBack),
+            _ => Err(RejectionReason::NotDisputed),
+        }
+    }
+}
+
+/// A disputable transaction's current dispute status (see `Engine::dispute_status`)
+#[derive(Clone,PartialEq,Debug,Serialize)]
+pub struct DisputeStatus {
+    pub client: u16,
+    pub tx: u32,
+    ///"deposit" or "withdrawal"
+    pub r#type: &'static str,
+    pub amount: Amount,
+    pub state: DisputeState,
+}
+
+/// A dispute that's still open (see `Engine::open_disputes`), `Serialize`-able as an open disputes report CSV line
+#[derive(Clone,PartialEq,Debug,Serialize)]
+pub struct OpenDispute {
+    pub client: u16,
+    pub tx: u32,
+    ///the disputed transaction's type: "deposit" or "withdrawal"
+    pub r#type: &'static str,
+    ///the held amount
+    pub amount: Amount,
+    ///the dispute's timestamp (None: untimed, or unknown for a dispute from an older snapshot)
+    pub opened_at: Option<Timestamp>,
+    ///the dispute's position in the engine's admitted transactions, counting from 1
+    /// (None: unknown, for a dispute from an older snapshot)
+    pub sequence: Option<u64>,
+}
+
+/// When a transaction was applied: its position in the engine's admitted transactions, and its record's timestamp (if any)
+#[derive(Copy,Clone,PartialEq,Default,Debug,Serialize,Deserialize)]
+pub(crate) struct Moment {
+    //the number of transactions the engine had admitted, including this one
+    sequence: u64,
+    timestamp: Option<Timestamp>,
+}
+
+/// A time limit after a transaction
+#[derive(Copy,Clone,Eq,PartialEq,Debug,Serialize,Deserialize)]
+pub enum Window {
+    /// A duration between timestamps: only enforced if both transactions are timestamped
+    Time(Duration),
+    /// A number of transactions admitted (on any client account) after it
+    Transactions(u64),
+}
+
+impl Window {
+
+    /// Whether `later` is past this window after `earlier`
+    fn has_expired(&self, earlier: Moment, later: Moment) -> bool {
+
+        match *self {
+            Window::Time(duration) => match (earlier.timestamp, later.timestamp) {
+                (Some(earlier), Some(later)) => i128::from(later.0) - i128::from(earlier.0) > duration.as_nanos() as i128,
+                _ => false,
+            },
+            Window::Transactions(transactions) => later.sequence.saturating_sub(earlier.sequence) > transactions,
+        }
+    }
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    /// Parses a duration in whole days, hours, minutes, or seconds (e.g. `120d`, `36h`, `90m`, `30s`),
+    /// or a number of transactions (e.g. `1000tx`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        let err = || format!("invalid window: {}", s);
+
+        if let Some(transactions) = s.strip_suffix("tx") {
+            return transactions.parse().map(Window::Transactions).map_err(|_| err());
+        }
+
+        let split = s.len().checked_sub(1).filter(|&split| s.is_char_boundary(split)).ok_or_else(err)?;
+        let (count, unit) = s.split_at(split);
+
+        let seconds = match unit {
+            "d" => 24 * 60 * 60,
+            "h" => 60 * 60,
+            "m" => 60,
+            "s" => 1,
+            _ => return Err(err()),
+        };
 
+        count.parse::<u64>().ok()
+            .and_then(|count| count.checked_mul(seconds))
+            .map(|seconds| Window::Time(Duration::from_secs(seconds)))
+            .ok_or_else(err)
     }
 }
 
-/// Processes an account's transaction history and returns its current state.
-/// Note: `client_id` is only used to create the AccountState:
-/// all `transactions` will be processed.
-fn process_account_transactions(client_id: ClientId, transactions: &[Transaction]) -> Option<AccountState> {
+/// Engine behavior options
+#[derive(Copy,Clone,Eq,PartialEq,Debug,Serialize,Deserialize)]
+pub struct EngineConfig {
+    /// Whether a transaction can be disputed again after its dispute is resolved
+    pub allow_redispute: bool,
+    /// If present: how long after a deposit or withdrawal it can be disputed
+    #[serde(default)]
+    pub dispute_window: Option<Window>,
+    /// If present: how long after a dispute it can be charged back
+    #[serde(default)]
+    pub chargeback_deadline: Option<Window>,
+}
+
+impl Default for EngineConfig {
+
+    fn default() -> Self {
+        Self { allow_redispute: true, dispute_window: None, chargeback_deadline: None }
+    }
+}
+
+/// A disputable transaction's record
+#[derive(Debug,Serialize,Deserialize)]
+struct DisputableTx {
+    disputable: Disputable,
+    state: DisputeState,
+    //when it was applied (None: unknown, for a transaction from an older snapshot)
+    #[serde(default)]
+    applied: Option<Moment>,
+    //when it was last disputed
+    #[serde(default)]
+    disputed: Option<Moment>,
+}
+
+impl DisputableTx {
+
+    fn new(disputable: Disputable, applied: Moment) -> Self {
+        Self { disputable, state: DisputeState::Settled, applied: Some(applied), disputed: None }
+    }
+}
+
+/// The processing state of a single client account
+#[derive(Debug,Serialize,Deserialize)]
+struct Account {
+
+    client_id: ClientId,
+
+    //None until the account is opened by its first deposit
+    state: Option<AccountState>,
+
+    //for existing deposits and successful withdrawals:
+    // transaction IDs mapped to amounts, dispute lifecycle states, and when they happened
+    disputable_transactions: HashMap<TxId, DisputableTx>,
+
+    //the timestamp of the last applied transaction that had one
+    #[serde(default)]
+    last_timestamp: Option<Timestamp>,
+
+    //not persisted: options are set for each run
+    #[serde(skip)]
+    config: EngineConfig,
+}
+
+impl Account {
 
-    let mut account_state: Option<AccountState> = None;
+    #[cfg(test)]
+    fn new(client_id: ClientId) -> Self {
+        Self::with_config(client_id, EngineConfig::default())
+    }
 
-    //for existing deposits: transaction IDs mapped to amounts
-    let mut deposit_amounts = HashMap::<TxId, Amount>::new();
+    fn with_config(client_id: ClientId, config: EngineConfig) -> Self {
+        Self {
+            client_id,
+            state: None,
+            disputable_transactions: HashMap::new(),
+            last_timestamp: None,
+            config,
+        }
+    }
 
-    //the transaction IDs of disputed deposits
-    let mut disputed_deposit_ids = HashSet::<TxId>::new();
+    /// Applies the next transaction in this account's history, with no time limits
+    #[cfg(test)]
+    fn apply(&mut self, transaction: &Transaction) -> Result<(), RejectionReason> {
+        self.apply_at(transaction, Moment::default())
+    }
 
-    for transaction in transactions {
+    /// Applies the next transaction in this account's history, which happened at `now`,
+    /// or returns the reason it was rejected
+    fn apply_at(&mut self, transaction: &Transaction, now: Moment) -> Result<(), RejectionReason> {
 
         // Create the account state on the first deposit:
         // No other transactions are valid until the account is opened by a deposit.
@@ -78,163 +412,460 @@ fn process_account_transactions(client_id: ClientId, transactions: &[Transaction
         // Note: this handling prevents an edge case bug in which an un-deposited account
         // could erroneously appear in the output after receiving non-deposit transactions:
         // such an account should be considered unopened, and therefore invalid.
-        // In this function, a client account that never receives a deposit will return 'None'.
-        if account_state.is_none() {
+        // A client account that never receives a deposit has no state ('None').
+        if self.state.is_none() {
             if let Transaction::Deposit(_,_) = transaction {
 
                 //this is the first deposit, so the account exists now
-                account_state = Some(AccountState {
-                    client_id,
-                    available: Amount(0.0),
-                    held: Amount(0.0),
+                self.state = Some(AccountState {
+                    client_id: self.client_id,
+                    available: Amount(0_0000),
+                    held: Amount(0_0000),
                     locked: false
                 });
             }
         }
 
-        let account_state = match account_state {
-            Some(ref mut a) => a,
-            None => {
-                //still waiting for the first deposit:
-                // don't process this transaction, it predates its target account
-                continue;
-            }
-        };
+        //if still waiting for the first deposit:
+        // don't process this transaction, it predates its target account
+        let account_state = self.state.as_mut().ok_or(RejectionReason::AccountNotOpen)?;
+
+        //once a client account is locked, no more actions are possible until it's unlocked:
+        // reject all other transactions
+        if account_state.locked && !matches!(transaction, Transaction::Unlock(_)) {
+            return Err(RejectionReason::AccountLocked);
+        }
 
         match *transaction {
 
             Transaction::Deposit(tx_id, amount) => {
 
-                //deposits always succeed
-                account_state.available.0 += amount.0;
+                //deposits succeed unless the account's funds would overflow
+                adjust_funds(account_state, amount, Amount(0))?;
 
-                //record this deposit, in case of a chargeback
-                // note: this assumes transaction ID uniqueness: no check for insert() overwrite
-                deposit_amounts.insert(tx_id, amount);
+                //record this deposit, in case of a dispute
+                // note: transaction ID uniqueness is enforced by the Engine: no check for insert() overwrite
+                self.disputable_transactions.insert(tx_id, DisputableTx::new(Disputable::Deposit(amount), now));
             },
 
-            Transaction::Withdrawal(_tx_id, amount) => {
+            Transaction::Withdrawal(tx_id, amount) => {
 
                 //withdrawals only happen if enough funds are available
-                if account_state.available.0 >= amount.0 {
-                    account_state.available.0 -= amount.0;
+                if account_state.available < amount {
+                    return Err(RejectionReason::InsufficientFunds);
                 }
+
+                adjust_funds(account_state, -amount, Amount(0))?;
+
+                //record this withdrawal, in case of a dispute
+                self.disputable_transactions.insert(tx_id, DisputableTx::new(Disputable::Withdrawal(amount), now));
             },
 
             Transaction::Dispute(tx_id) => {
 
-                //disputes only happen on existing deposits
-                if let Some(&amount) = deposit_amounts.get(&tx_id) {
+                //disputes only happen on existing transactions, in a state that allows them, within the dispute window
+                let disputed = self.disputable_transactions.get_mut(&tx_id)
+                    .ok_or(RejectionReason::UnknownTx)?;
 
-                    //hold the disputed funds
-                    account_state.available.0 -= amount.0;
-                    account_state.held.0 += amount.0;
+                let state = disputed.state.dispute(&self.config)?;
+
+                if let (Some(window), Some(applied)) = (self.config.dispute_window, disputed.applied) {
+                    if window.has_expired(applied, now) {
+                        return Err(RejectionReason::DisputeWindowExpired);
+                    }
+                }
 
-                    //record the disputed status
-                    // note: this assumes transaction ID uniqueness: no check for insert() overwrite
-                    disputed_deposit_ids.insert(tx_id);
+                match disputed.disputable {
+                    //hold the disputed funds
+                    Disputable::Deposit(amount) => adjust_funds(account_state, -amount, amount)?,
+                    //hold the withdrawn funds that the client claims back:
+                    // they haven't been returned to the account (yet)
+                    Disputable::Withdrawal(amount) => adjust_funds(account_state, Amount(0), amount)?,
                 }
+
+                disputed.state = state;
+                disputed.disputed = Some(now);
             },
 
             Transaction::Resolve(tx_id) => {
 
-                //resolve only applies to an existing disputed deposit
-                if let Some(&amount) = deposit_amounts.get(&tx_id) {
-
-                    if disputed_deposit_ids.contains(&tx_id) {
+                //resolve only applies to an existing disputed transaction
+                let disputed = self.disputable_transactions.get_mut(&tx_id)
+                    .ok_or(RejectionReason::UnknownTx)?;
 
-                        //make the disputed funds available
-                        account_state.available.0 += amount.0;
-                        account_state.held.0 -= amount.0;
-                    }
+                let state = disputed.state.resolve()?;
 
-                    //remove the disputed status
-                    disputed_deposit_ids.remove(&tx_id);
+                match disputed.disputable {
+                    //make the disputed funds available
+                    Disputable::Deposit(amount) => adjust_funds(account_state, amount, -amount)?,
+                    //the withdrawal stands: release the claimed funds
+                    Disputable::Withdrawal(amount) => adjust_funds(account_state, Amount(0), -amount)?,
                 }
+
+                disputed.state = state;
             },
 
             Transaction::Chargeback(tx_id) => {
 
-                //chargeback only applies to an existing disputed deposit
-                if let Some(&amount) = deposit_amounts.get(&tx_id) {
+                //chargeback only applies to an existing disputed transaction, before the chargeback deadline
+                let disputed = self.disputable_transactions.get_mut(&tx_id)
+                    .ok_or(RejectionReason::UnknownTx)?;
+
+                let state = disputed.state.chargeback()?;
+
+                if let (Some(deadline), Some(dispute)) = (self.config.chargeback_deadline, disputed.disputed) {
+                    if deadline.has_expired(dispute, now) {
+                        return Err(RejectionReason::ChargebackDeadlinePassed);
+                    }
+                }
+
+                match disputed.disputable {
+                    //remove the chargeback withdrawal from held funds
+                    Disputable::Deposit(amount) => adjust_funds(account_state, Amount(0), -amount)?,
+                    //reverse the withdrawal: the claimed funds are returned to the client
+                    Disputable::Withdrawal(amount) => adjust_funds(account_state, amount, -amount)?,
+                }
 
-                    if disputed_deposit_ids.contains(&tx_id) {
+                disputed.state = state;
 
-                        //remove the chargeback withdrawal from held funds
-                        account_state.held.0 -= amount.0;
+                //lock (also "freeze") this account
+                account_state.locked = true;
+            },
 
-                        //lock (also "freeze") this account
-                        account_state.locked = true;
+            Transaction::Unlock(_tx_id) => {
 
-                        //now that the client account is locked, no more actions are possible:
-                        //ignore all remaining transactions
-                        break;
-                    }
+                //reopen a reviewed account
+                if !account_state.locked {
+                    return Err(RejectionReason::NotLocked);
                 }
+
+                account_state.locked = false;
+            },
+
+            Transaction::Freeze(_tx_id) => {
+
+                //proactively lock this account (already-locked accounts were rejected above)
+                account_state.locked = true;
             },
 
         }
+
+        Ok(())
+    }
+}
+
+/// A long-lived transaction processor:
+/// applies transactions one at a time, as they arrive,
+/// and can report the current state of every client account at any point
+#[derive(Default,Debug,Serialize,Deserialize)]
+pub struct Engine {
+
+    //client accounts that have received any transaction, by client ID
+    accounts: BTreeMap<ClientId, Account>,
+
+    //the IDs of every deposit, withdrawal, and admin action seen so far, on any client account
+    tx_ids: HashSet<TxId>,
+
+    //the number of transactions admitted so far, on any client account
+    #[serde(default)]
+    sequence: u64,
+
+    //the sequence number of the last journal entry applied (0: none)
+    #[serde(default)]
+    pub(crate) journal_sequence: u64,
+
+    //not persisted: options are set for each run
+    #[serde(skip)]
+    config: EngineConfig,
+}
+
+impl Engine {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: EngineConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    /// Applies the next transaction record to its client account.
+    /// A record that can't be applied has no effect, and its Rejection is returned.
+    pub fn apply(&mut self, record: &InputRecord) -> Result<(), Rejection> {
+
+        let (client_id, transaction, now) = self.admit(record)?;
+
+        self.apply_admitted(client_id, &transaction, now)
+            .map_err(|reason| Rejection::new(record, reason))
+    }
+
+    /// The checks that span client accounts: parses the record and checks its transaction ID.
+    /// Returns the admitted transaction, and when it happened.
+    pub(crate) fn admit(&mut self, record: &InputRecord) -> Result<(ClientId, Transaction, Moment), Rejection> {
+
+        let (client_id, transaction) = parse_record(record)
+            .map_err(|e| Rejection::new(record, RejectionReason::InvalidRecord(e.kind())))?;
+
+        //transaction IDs are globally unique: a reused ID indicates a replayed transaction.
+        // Each ID is recorded on first sight, even if its transaction is rejected.
+        if let Transaction::Deposit(tx_id, _) | Transaction::Withdrawal(tx_id, _) |
+               Transaction::Unlock(tx_id) | Transaction::Freeze(tx_id) = transaction {
+            if !self.tx_ids.insert(tx_id) {
+                return Err(Rejection::new(record, RejectionReason::DuplicateTx));
+            }
+        }
+
+        self.sequence += 1;
+
+        Ok((client_id, transaction, Moment { sequence: self.sequence, timestamp: record.timestamp }))
+    }
+
+    /// Applies an admitted transaction, which happened at `now`, to its client account
+    pub(crate) fn apply_admitted(&mut self, client_id: ClientId, transaction: &Transaction, now: Moment)
+        -> Result<(), RejectionReason> {
+
+        let config = self.config;
+
+        let account = self.accounts.entry(client_id)
+            .or_insert_with(|| Account::with_config(client_id, config));
+
+        //each client's timestamped transactions must be in time order (untimed ones aren't checked)
+        if let (Some(timestamp), Some(last_timestamp)) = (now.timestamp, account.last_timestamp) {
+            if timestamp < last_timestamp {
+                return Err(RejectionReason::OutOfOrder);
+            }
+        }
+
+        account.apply_at(transaction, now)?;
+
+        if now.timestamp.is_some() {
+            account.last_timestamp = now.timestamp;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn config(&self) -> EngineConfig {
+        self.config
+    }
+
+    /// Sets the config of this engine and its client accounts
+    pub(crate) fn set_config(&mut self, config: EngineConfig) {
+
+        self.config = config;
+
+        for account in self.accounts.values_mut() {
+            account.config = config;
+        }
+    }
+
+    /// Moves the client accounts into `shards` new engines (with this engine's config),
+    /// choosing each account's engine with `shard_of`
+    pub(crate) fn split_accounts<F: Fn(ClientId) -> usize>(&mut self, shards: usize, shard_of: F) -> Vec<Engine> {
+
+        let mut engines: Vec<Engine> = (0..shards).map(|_| Engine::with_config(self.config)).collect();
+
+        for (client_id, account) in std::mem::take(&mut self.accounts) {
+            engines[shard_of(client_id)].accounts.insert(client_id, account);
+        }
+
+        engines
+    }
+
+    /// Moves the client accounts of `other` into this engine
+    pub(crate) fn merge_accounts(&mut self, other: Engine) {
+        self.accounts.extend(other.accounts);
+    }
+
+    /// Returns the current state of each opened client account, ordered by client ID
+    pub fn snapshot(&self) -> Vec<AccountState> {
+
+        self.accounts.values().filter_map(|account| account.state.clone()).collect()
     }
 
-    account_state
+    /// Returns the current state of a client account, if it's been opened
+    pub fn account_state(&self, client_id: ClientId) -> Option<AccountState> {
+
+        self.accounts.get(&client_id).and_then(|account| account.state.clone())
+    }
+
+    /// Returns the dispute status of a deposit or successful withdrawal, if there is one with this transaction ID
+    pub fn dispute_status(&self, tx: u32) -> Option<DisputeStatus> {
+
+        self.accounts.values().find_map(|account| {
+            account.disputable_transactions.get(&TxId(tx)).map(|&DisputableTx { disputable, state, .. }| {
+                let (r#type, amount) = disputable.type_and_amount();
+                DisputeStatus { client: account.client_id.0, tx, r#type, amount, state }
+            })
+        })
+    }
+
+    /// Returns every dispute that hasn't been resolved or charged back, ordered by client ID and transaction ID
+    pub fn open_disputes(&self) -> Vec<OpenDispute> {
+
+        let mut open_disputes: Vec<OpenDispute> = self.accounts.values().flat_map(|account| {
+            account.disputable_transactions.iter()
+                .filter(|(_, disputable_tx)| disputable_tx.state == DisputeState::Disputed)
+                .map(move |(&TxId(tx), disputable_tx)| {
+                    let (r#type, amount) = disputable_tx.disputable.type_and_amount();
+                    OpenDispute {
+                        client: account.client_id.0,
+                        tx,
+                        r#type,
+                        amount,
+                        opened_at: disputable_tx.disputed.and_then(|disputed| disputed.timestamp),
+                        sequence: disputable_tx.disputed.map(|disputed| disputed.sequence),
+                    }
+                })
+        }).collect();
+
+        open_disputes.sort_by_key(|open_dispute| (open_dispute.client, open_dispute.tx));
+        open_disputes
+    }
 }
 
 ///Processes a history of transactions:
-/// calculates and returns the resulting state of each client account
-pub fn run(records: &[InputRecord]) -> Vec<AccountState> {
+/// calculates and returns the resulting state of each client account.
+/// Rejected transactions are ignored.
+pub fn run<I: IntoIterator<Item = InputRecord>>(records: I) -> Vec<AccountState> {
+
+    match try_run(records.into_iter().map(Ok::<_, Infallible>)) {
+        Ok(account_states) => account_states,
+        Err(e) => match e {},
+    }
+}
 
-    //maps a client ID to an ordered sequence of transactions on its account
-    let mut account_histories = BTreeMap::<ClientId, Vec<Transaction>>::new();
+///Processes a history of transactions, as read from a fallible source (like `input::read_csv`):
+/// stops at the first error and returns it,
+/// otherwise calculates and returns the resulting state of each client account
+pub fn try_run<I, E>(records: I) -> Result<Vec<AccountState>, E>
+    where I: IntoIterator<Item = Result<InputRecord, E>> {
+
+    let mut engine = Engine::new();
 
     for record in records {
+        let _ = engine.apply(&record?);
+    }
 
-        match parse_record(record) {
-            Ok((client_id, transaction)) => {
+    Ok(engine.snapshot())
+}
 
-                //add this transaction to the client ID's transaction sequence
-                account_histories.entry(client_id).or_default().push(transaction);
-            },
+/// A point in the input, for point-in-time account states: records are applied up to and including it
+#[derive(Copy,Clone,Eq,PartialEq,Debug)]
+pub enum AsOf {
+    /// An input line number
+    Line(u64),
+    /// The first record with this transaction ID (e.g. a deposit, rather than its dispute)
+    Tx(u32),
+}
+
+/// Tracks whether input is up to an `AsOf` point
+#[derive(Clone,Debug)]
+pub struct AsOfLimit {
+    as_of: AsOf,
+    //whether every later input item is past the point
+    reached: bool,
+}
+
+impl AsOfLimit {
+
+    pub fn new(as_of: AsOf) -> Self {
+        Self { as_of, reached: false }
+    }
+
+    /// Whether the next input item (a record, or an invalid line) is up to the point:
+    /// once this is false, it's false for every later item
+    pub fn includes(&mut self, item: &Result<InputRecord, InputError>) -> bool {
+
+        match item {
+            Ok(record) => self.includes_at(record.line, Some(record.tx)),
+            //an invalid line's transaction ID may be unknown
+            Err(e) => self.includes_at(e.line(), None),
+        }
+    }
+
+    /// Whether every later input item is past the point
+    pub fn is_reached(&self) -> bool {
+        self.reached
+    }
 
-            //The spec doesn't specify an error-reporting channel. What could be done here?
-            // For now, just ignore invalid records.
-            Err(_) => {},
+    fn includes_at(&mut self, line: u64, tx: Option<u32>) -> bool {
+
+        if self.reached {
+            return false;
+        }
+
+        match self.as_of {
+            AsOf::Line(last_line) => {
+                self.reached = line >= last_line;
+                line <= last_line
+            },
+            AsOf::Tx(last_tx) => {
+                self.reached = tx == Some(last_tx);
+                true
+            },
         }
     }
+}
+
+///Processes a history of transactions up to a point (see `AsOf`):
+/// calculates and returns the state of each client account at that point.
+/// Rejected transactions are ignored.
+pub fn run_as_of<I: IntoIterator<Item = InputRecord>>(records: I, as_of: AsOf) -> Vec<AccountState> {
+
+    let mut limit = AsOfLimit::new(as_of);
 
-    //process the histories of the client accounts:
-    // generate an AccountState for each
-    account_histories.iter().filter_map(|(&client_id, transactions)| {
-        process_account_transactions(client_id, transactions)
-    }).collect()
+    run(records.into_iter().take_while(|record| limit.includes_at(record.line, Some(record.tx))))
 }
 
+///Processes a history of transactions as of a cutoff time:
+/// records timestamped after it are skipped (untimed records aren't),
+/// then the state of each client account is calculated and returned.
+/// Rejected transactions are ignored.
+pub fn run_to_cutoff<I: IntoIterator<Item = InputRecord>>(records: I, cutoff: Timestamp) -> Vec<AccountState> {
+
+    run(records.into_iter().filter(|record| !record.is_after(cutoff)))
+}
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    /// Processes an account's transaction history and returns its current state.
+    /// Note: `client_id` is only used to create the AccountState:
+    /// all `transactions` will be processed.
+    fn process_account_transactions(client_id: ClientId, transactions: &[Transaction]) -> Option<AccountState> {
+
+        let mut account = Account::new(client_id);
+
+        for transaction in transactions {
+            let _ = account.apply(transaction);
+        }
+
+        account.state
+    }
+
     #[test]
     fn parse_record_test() {
 
         //success: deposit
         {
-            let record = InputRecord{r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(3.0)};
+            let record = InputRecord{r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(3_0000)), line: 0, timestamp: None};
             let result = parse_record(&record).unwrap();
 
-            assert_eq!(result, (ClientId(1), Transaction::Deposit(TxId(2), Amount(3.0))));
+            assert_eq!(result, (ClientId(1), Transaction::Deposit(TxId(2), Amount(3_0000))));
         }
 
         //success: withdrawal
         {
-            let record = InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 2, amount: Some(3.0)};
+            let record = InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 2, amount: Some(Amount(3_0000)), line: 0, timestamp: None};
             let result = parse_record(&record).unwrap();
 
-            assert_eq!(result, (ClientId(1), Transaction::Withdrawal(TxId(2), Amount(3.0))));
+            assert_eq!(result, (ClientId(1), Transaction::Withdrawal(TxId(2), Amount(3_0000))));
         }
 
         //success: dispute
         {
-            let record = InputRecord{r#type: "dispute".to_string(), client: 1, tx: 2, amount: None};
+            let record = InputRecord{r#type: "dispute".to_string(), client: 1, tx: 2, amount: None, line: 0, timestamp: None};
             let result = parse_record(&record).unwrap();
 
             assert_eq!(result, (ClientId(1), Transaction::Dispute(TxId(2))));
@@ -242,7 +873,7 @@ mod test {
 
         //success: resolve
         {
-            let record = InputRecord{r#type: "resolve".to_string(), client: 1, tx: 2, amount: None};
+            let record = InputRecord{r#type: "resolve".to_string(), client: 1, tx: 2, amount: None, line: 0, timestamp: None};
             let result = parse_record(&record).unwrap();
 
             assert_eq!(result, (ClientId(1), Transaction::Resolve(TxId(2))));
@@ -250,34 +881,71 @@ mod test {
 
         //success: chargeback
         {
-            let record = InputRecord{r#type: "chargeback".to_string(), client: 1, tx: 2, amount: None};
+            let record = InputRecord{r#type: "chargeback".to_string(), client: 1, tx: 2, amount: None, line: 0, timestamp: None};
             let result = parse_record(&record).unwrap();
 
             assert_eq!(result, (ClientId(1), Transaction::Chargeback(TxId(2))));
         }
 
+        //success: unlock
+        {
+            let record = InputRecord{r#type: "unlock".to_string(), client: 1, tx: 2, amount: None, line: 0, timestamp: None};
+            let result = parse_record(&record).unwrap();
+
+            assert_eq!(result, (ClientId(1), Transaction::Unlock(TxId(2))));
+        }
+
+        //success: freeze
+        {
+            let record = InputRecord{r#type: "freeze".to_string(), client: 1, tx: 2, amount: None, line: 0, timestamp: None};
+            let result = parse_record(&record).unwrap();
+
+            assert_eq!(result, (ClientId(1), Transaction::Freeze(TxId(2))));
+        }
+
         //failure: nonexistent transaction type
         {
-            let record = InputRecord{r#type: "no_such_tx_type".to_string(), client: 1, tx: 2, amount: None};
+            let record = InputRecord{r#type: "no_such_tx_type".to_string(), client: 1, tx: 2, amount: None, line: 5, timestamp: None};
             let result = parse_record(&record);
 
-            assert!(matches!(result, Err(_)));
+            assert_eq!(result, Err(InputError::UnknownType { line: 5, raw: "no_such_tx_type,1,2,".to_string() }));
         }
 
         //failure: deposit is missing its amount
         {
-            let record = InputRecord{r#type: "deposit".to_string(), client: 1, tx: 2, amount: None};
+            let record = InputRecord{r#type: "deposit".to_string(), client: 1, tx: 2, amount: None, line: 5, timestamp: None};
+            let result = parse_record(&record);
+
+            assert_eq!(result, Err(InputError::MissingAmount { line: 5, raw: "deposit,1,2,".to_string() }));
+        }
+
+        //failure: deposit or withdrawal amount isn't positive
+        {
+            let record = InputRecord{r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(-5_0000)), line: 5, timestamp: None};
+            let result = parse_record(&record);
+
+            assert_eq!(result, Err(InputError::NonPositiveAmount { line: 5, raw: "deposit,1,2,-5.0000".to_string() }));
+
+            let record = InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 2, amount: Some(Amount(0)), line: 5, timestamp: None};
             let result = parse_record(&record);
 
-            assert!(matches!(result, Err(_)));
+            assert_eq!(result, Err(InputError::NonPositiveAmount { line: 5, raw: "withdrawal,1,2,0.0000".to_string() }));
         }
 
         //failure: dispute has an amount
         {
-            let record = InputRecord{r#type: "dispute".to_string(), client: 1, tx: 2, amount: Some(3.0)};
+            let record = InputRecord{r#type: "dispute".to_string(), client: 1, tx: 2, amount: Some(Amount(3_0000)), line: 5, timestamp: None};
+            let result = parse_record(&record);
+
+            assert_eq!(result, Err(InputError::UnexpectedAmount { line: 5, raw: "dispute,1,2,3.0000".to_string() }));
+        }
+
+        //failure: freeze has an amount
+        {
+            let record = InputRecord{r#type: "freeze".to_string(), client: 1, tx: 2, amount: Some(Amount(3_0000)), line: 5, timestamp: None};
             let result = parse_record(&record);
 
-            assert!(matches!(result, Err(_)));
+            assert_eq!(result, Err(InputError::UnexpectedAmount { line: 5, raw: "freeze,1,2,3.0000".to_string() }));
         }
     }
 
@@ -300,7 +968,7 @@ mod test {
         //account was never opened by a deposit
         {
             let transactions = vec![
-                Transaction::Withdrawal(TxId(3), Amount(2.0)),
+                Transaction::Withdrawal(TxId(3), Amount(2_0000)),
                 Transaction::Dispute(TxId(3)),
                 Transaction::Resolve(TxId(3)),
             ];
@@ -315,16 +983,16 @@ mod test {
         //deposits + withdrawals (all successful)
         {
             let transactions = vec![
-                Transaction::Deposit(TxId(1), Amount(10.0)),
-                Transaction::Deposit(TxId(2), Amount(1.0)),
-                Transaction::Withdrawal(TxId(3), Amount(2.0)),
-                Transaction::Deposit(TxId(4), Amount(1.0)),
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
+                Transaction::Deposit(TxId(2), Amount(1_0000)),
+                Transaction::Withdrawal(TxId(3), Amount(2_0000)),
+                Transaction::Deposit(TxId(4), Amount(1_0000)),
             ];
 
             let expected = Some(AccountState {
                 client_id,
-                available: Amount(10.0),
-                held: Amount(0.0),
+                available: Amount(10_0000),
+                held: Amount(0_0000),
                 locked: false
             });
 
@@ -336,15 +1004,15 @@ mod test {
         //overdrawing withdrawal rejected
         {
             let transactions = vec![
-                Transaction::Deposit(TxId(1), Amount(1.0)),
-                Transaction::Withdrawal(TxId(2), Amount(2.0)),
-                Transaction::Deposit(TxId(3), Amount(1.0)),
+                Transaction::Deposit(TxId(1), Amount(1_0000)),
+                Transaction::Withdrawal(TxId(2), Amount(2_0000)),
+                Transaction::Deposit(TxId(3), Amount(1_0000)),
             ];
 
             let expected = Some(AccountState {
                 client_id,
-                available: Amount(2.0),
-                held: Amount(0.0),
+                available: Amount(2_0000),
+                held: Amount(0_0000),
                 locked: false
             });
 
@@ -356,14 +1024,14 @@ mod test {
         //pending dispute (neither resolved nor charged back)
         {
             let transactions = vec![
-                Transaction::Deposit(TxId(1), Amount(10.0)),
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
                 Transaction::Dispute(TxId(1)),
             ];
 
             let expected = Some(AccountState {
                 client_id,
-                available: Amount(0.0),
-                held: Amount(10.0),
+                available: Amount(0_0000),
+                held: Amount(10_0000),
                 locked: false
             });
 
@@ -375,15 +1043,15 @@ mod test {
         //pending dispute with negative balance
         {
             let transactions = vec![
-                Transaction::Deposit(TxId(1), Amount(10.0)),
-                Transaction::Withdrawal(TxId(1), Amount(8.0)),
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
+                Transaction::Withdrawal(TxId(2), Amount(8_0000)),
                 Transaction::Dispute(TxId(1)),
             ];
 
             let expected = Some(AccountState {
                 client_id,
-                available: Amount(-8.0),
-                held: Amount(10.0),
+                available: Amount(-8_0000),
+                held: Amount(10_0000),
                 locked: false
             });
 
@@ -395,16 +1063,16 @@ mod test {
         //chargeback resulting in negative balance
         {
             let transactions = vec![
-                Transaction::Deposit(TxId(1), Amount(10.0)),
-                Transaction::Withdrawal(TxId(1), Amount(8.0)),
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
+                Transaction::Withdrawal(TxId(2), Amount(8_0000)),
                 Transaction::Dispute(TxId(1)),
                 Transaction::Chargeback(TxId(1)),
             ];
 
             let expected = Some(AccountState {
                 client_id,
-                available: Amount(-8.0),
-                held: Amount(0.0),
+                available: Amount(-8_0000),
+                held: Amount(0_0000),
                 locked: true
             });
 
@@ -416,15 +1084,15 @@ mod test {
         //resolved dispute
         {
             let transactions = vec![
-                Transaction::Deposit(TxId(1), Amount(10.0)),
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
                 Transaction::Dispute(TxId(1)),
                 Transaction::Resolve(TxId(1)),
             ];
 
             let expected = Some(AccountState {
                 client_id,
-                available: Amount(10.0),
-                held: Amount(0.0),
+                available: Amount(10_0000),
+                held: Amount(0_0000),
                 locked: false
             });
 
@@ -436,21 +1104,21 @@ mod test {
         //multiple resolved disputes with interlaced deposits
         {
             let transactions = vec![
-                Transaction::Deposit(TxId(1), Amount(100.0)),
+                Transaction::Deposit(TxId(1), Amount(100_0000)),
                 Transaction::Dispute(TxId(1)),
                 Transaction::Resolve(TxId(1)),
                 Transaction::Dispute(TxId(1)),
-                Transaction::Deposit(TxId(2), Amount(10.0)),
+                Transaction::Deposit(TxId(2), Amount(10_0000)),
                 Transaction::Resolve(TxId(1)),
                 Transaction::Dispute(TxId(2)),
-                Transaction::Deposit(TxId(3), Amount(1.0)),
+                Transaction::Deposit(TxId(3), Amount(1_0000)),
                 Transaction::Resolve(TxId(2)),
             ];
 
             let expected = Some(AccountState {
                 client_id,
-                available: Amount(111.0),
-                held: Amount(0.0),
+                available: Amount(111_0000),
+                held: Amount(0_0000),
                 locked: false
             });
 
@@ -462,20 +1130,20 @@ mod test {
         //chargeback with blocked subsequent transaction attempts
         {
             let transactions = vec![
-                Transaction::Deposit(TxId(1), Amount(10.0)),
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
                 Transaction::Dispute(TxId(1)),
                 Transaction::Chargeback(TxId(1)),
 
                 //remaining transactions will not happen (account is locked/frozen)
                 Transaction::Resolve(TxId(1)),
-                Transaction::Deposit(TxId(1), Amount(100.0)),
-                Transaction::Withdrawal(TxId(1), Amount(5.0)),
+                Transaction::Deposit(TxId(1), Amount(100_0000)),
+                Transaction::Withdrawal(TxId(1), Amount(5_0000)),
             ];
 
             let expected = Some(AccountState {
                 client_id,
-                available: Amount(0.0),
-                held: Amount(0.0),
+                available: Amount(0_0000),
+                held: Amount(0_0000),
                 locked: true
             });
 
@@ -493,13 +1161,13 @@ mod test {
                 Transaction::Resolve(TxId(1)),
                 //
 
-                Transaction::Deposit(TxId(1), Amount(10.0)),
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
             ];
 
             let expected = Some(AccountState {
                 client_id,
-                available: Amount(10.0),
-                held: Amount(0.0),
+                available: Amount(10_0000),
+                held: Amount(0_0000),
                 locked: false
             });
 
@@ -508,14 +1176,43 @@ mod test {
             assert_eq!(result, expected);
         }
 
-        //dispute resolution precedes dispute
+        //overflowing transactions are ignored
         {
             let transactions = vec![
-                Transaction::Deposit(TxId(1), Amount(10.0)),
+                Transaction::Deposit(TxId(1), Amount(i64::MAX - 1)),
+                Transaction::Withdrawal(TxId(2), Amount(i64::MAX - 1)),
+                Transaction::Deposit(TxId(3), Amount(1)),
+                Transaction::Dispute(TxId(1)),
+                Transaction::Deposit(TxId(4), Amount(i64::MAX - 1)),
 
-                //these transactions have no effect, their target isn't disputed yet
-                Transaction::Chargeback(TxId(1)),
-                Transaction::Resolve(TxId(1)),
+                //total funds would overflow
+                Transaction::Deposit(TxId(5), Amount(1)),
+
+                //held funds would overflow
+                Transaction::Dispute(TxId(4)),
+            ];
+
+            let expected = Some(AccountState {
+                client_id,
+                available: Amount(1),
+                held: Amount(i64::MAX - 1),
+                locked: false
+            });
+
+            let result = process_account_transactions(client_id, &transactions);
+
+            assert_eq!(result, expected);
+        }
+
+
+        //dispute resolution precedes dispute
+        {
+            let transactions = vec![
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
+
+                //these transactions have no effect, their target isn't disputed yet
+                Transaction::Chargeback(TxId(1)),
+                Transaction::Resolve(TxId(1)),
                 //
 
                 Transaction::Dispute(TxId(1)),
@@ -523,8 +1220,128 @@ mod test {
 
             let expected = Some(AccountState {
                 client_id,
-                available: Amount(0.0),
-                held: Amount(10.0),
+                available: Amount(0_0000),
+                held: Amount(10_0000),
+                locked: false
+            });
+
+            let result = process_account_transactions(client_id, &transactions);
+
+            assert_eq!(result, expected);
+        }
+    }
+
+
+    #[test]
+    fn withdrawal_dispute_test() {
+
+        let client_id = ClientId(1);
+
+        //pending dispute: the withdrawn funds are held
+        {
+            let transactions = vec![
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
+                Transaction::Withdrawal(TxId(2), Amount(4_0000)),
+                Transaction::Dispute(TxId(2)),
+            ];
+
+            let expected = Some(AccountState {
+                client_id,
+                available: Amount(6_0000),
+                held: Amount(4_0000),
+                locked: false
+            });
+
+            let result = process_account_transactions(client_id, &transactions);
+
+            assert_eq!(result, expected);
+        }
+
+        //resolved dispute: the withdrawal stands
+        {
+            let transactions = vec![
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
+                Transaction::Withdrawal(TxId(2), Amount(4_0000)),
+                Transaction::Dispute(TxId(2)),
+                Transaction::Resolve(TxId(2)),
+            ];
+
+            let expected = Some(AccountState {
+                client_id,
+                available: Amount(6_0000),
+                held: Amount(0_0000),
+                locked: false
+            });
+
+            let result = process_account_transactions(client_id, &transactions);
+
+            assert_eq!(result, expected);
+        }
+
+        //chargeback: the withdrawal is reversed and the account is locked
+        {
+            let transactions = vec![
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
+                Transaction::Withdrawal(TxId(2), Amount(4_0000)),
+                Transaction::Dispute(TxId(2)),
+                Transaction::Chargeback(TxId(2)),
+            ];
+
+            let expected = Some(AccountState {
+                client_id,
+                available: Amount(10_0000),
+                held: Amount(0_0000),
+                locked: true
+            });
+
+            let result = process_account_transactions(client_id, &transactions);
+
+            assert_eq!(result, expected);
+        }
+
+        //a rejected (overdrawing) withdrawal can't be disputed
+        {
+            let mut account = Account::new(client_id);
+
+            assert_eq!(account.apply(&Transaction::Deposit(TxId(1), Amount(1_0000))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Withdrawal(TxId(2), Amount(4_0000))), Err(RejectionReason::InsufficientFunds));
+            assert_eq!(account.apply(&Transaction::Dispute(TxId(2))), Err(RejectionReason::UnknownTx));
+            assert_eq!(account.apply(&Transaction::Resolve(TxId(2))), Err(RejectionReason::UnknownTx));
+            assert_eq!(account.apply(&Transaction::Chargeback(TxId(2))), Err(RejectionReason::UnknownTx));
+        }
+
+        //an undisputed withdrawal can't be resolved or charged back
+        {
+            let mut account = Account::new(client_id);
+
+            assert_eq!(account.apply(&Transaction::Deposit(TxId(1), Amount(10_0000))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Withdrawal(TxId(2), Amount(4_0000))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Resolve(TxId(2))), Err(RejectionReason::NotDisputed));
+            assert_eq!(account.apply(&Transaction::Chargeback(TxId(2))), Err(RejectionReason::NotDisputed));
+        }
+    }
+
+
+    #[test]
+    fn admin_transaction_test() {
+
+        let client_id = ClientId(1);
+
+        //unlock after a chargeback: the account is usable again
+        {
+            let transactions = vec![
+                Transaction::Deposit(TxId(1), Amount(10_0000)),
+                Transaction::Deposit(TxId(2), Amount(3_0000)),
+                Transaction::Dispute(TxId(1)),
+                Transaction::Chargeback(TxId(1)),
+                Transaction::Unlock(TxId(3)),
+                Transaction::Withdrawal(TxId(4), Amount(1_0000)),
+            ];
+
+            let expected = Some(AccountState {
+                client_id,
+                available: Amount(2_0000),
+                held: Amount(0_0000),
                 locked: false
             });
 
@@ -532,6 +1349,146 @@ mod test {
 
             assert_eq!(result, expected);
         }
+
+        //freeze: subsequent transactions are rejected until an unlock
+        {
+            let mut account = Account::new(client_id);
+
+            assert_eq!(account.apply(&Transaction::Deposit(TxId(1), Amount(10_0000))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Freeze(TxId(2))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Withdrawal(TxId(3), Amount(1_0000))), Err(RejectionReason::AccountLocked));
+            assert_eq!(account.apply(&Transaction::Freeze(TxId(4))), Err(RejectionReason::AccountLocked));
+            assert_eq!(account.state.as_ref().map(|state| state.locked), Some(true));
+
+            assert_eq!(account.apply(&Transaction::Unlock(TxId(5))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Withdrawal(TxId(6), Amount(1_0000))), Ok(()));
+            assert_eq!(account.state, Some(AccountState {
+                client_id,
+                available: Amount(9_0000),
+                held: Amount(0_0000),
+                locked: false
+            }));
+        }
+
+        //failure: unlock on an account that isn't locked
+        {
+            let mut account = Account::new(client_id);
+
+            assert_eq!(account.apply(&Transaction::Deposit(TxId(1), Amount(10_0000))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Unlock(TxId(2))), Err(RejectionReason::NotLocked));
+        }
+
+        //failure: admin actions on an account that isn't open
+        {
+            let mut account = Account::new(client_id);
+
+            assert_eq!(account.apply(&Transaction::Freeze(TxId(1))), Err(RejectionReason::AccountNotOpen));
+            assert_eq!(account.apply(&Transaction::Unlock(TxId(2))), Err(RejectionReason::AccountNotOpen));
+        }
+    }
+
+
+    #[test]
+    fn dispute_state_test() {
+
+        let allow = EngineConfig { allow_redispute: true, ..EngineConfig::default() };
+        let disallow = EngineConfig { allow_redispute: false, ..EngineConfig::default() };
+
+        //dispute
+        assert_eq!(DisputeState::Settled.dispute(&disallow), Ok(DisputeState::Disputed));
+        assert_eq!(DisputeState::Disputed.dispute(&allow), Err(RejectionReason::AlreadyDisputed));
+        assert_eq!(DisputeState::Resolved.dispute(&allow), Ok(DisputeState::Disputed));
+        assert_eq!(DisputeState::Resolved.dispute(&disallow), Err(RejectionReason::AlreadyResolved));
+        assert_eq!(DisputeState::ChargedBack.dispute(&allow), Err(RejectionReason::AlreadyChargedBack));
+
+        //resolve
+        assert_eq!(DisputeState::Settled.resolve(), Err(RejectionReason::NotDisputed));
+        assert_eq!(DisputeState::Disputed.resolve(), Ok(DisputeState::Resolved));
+        assert_eq!(DisputeState::Resolved.resolve(), Err(RejectionReason::NotDisputed));
+        assert_eq!(DisputeState::ChargedBack.resolve(), Err(RejectionReason::NotDisputed));
+
+        //chargeback
+        assert_eq!(DisputeState::Settled.chargeback(), Err(RejectionReason::NotDisputed));
+        assert_eq!(DisputeState::Disputed.chargeback(), Ok(DisputeState::ChargedBack));
+        assert_eq!(DisputeState::Resolved.chargeback(), Err(RejectionReason::NotDisputed));
+        assert_eq!(DisputeState::ChargedBack.chargeback(), Err(RejectionReason::NotDisputed));
+    }
+
+    #[test]
+    fn dispute_lifecycle_test() {
+
+        let client_id = ClientId(1);
+
+        //a repeated dispute doesn't hold the funds again
+        {
+            let mut account = Account::new(client_id);
+
+            assert_eq!(account.apply(&Transaction::Deposit(TxId(1), Amount(10_0000))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Dispute(TxId(1))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Dispute(TxId(1))), Err(RejectionReason::AlreadyDisputed));
+
+            assert_eq!(account.state, Some(AccountState {
+                client_id,
+                available: Amount(0_0000),
+                held: Amount(10_0000),
+                locked: false
+            }));
+        }
+
+        //re-disputing a resolved transaction, when allowed (the default)
+        {
+            let mut account = Account::new(client_id);
+
+            assert_eq!(account.apply(&Transaction::Deposit(TxId(1), Amount(10_0000))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Dispute(TxId(1))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Resolve(TxId(1))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Resolve(TxId(1))), Err(RejectionReason::NotDisputed));
+            assert_eq!(account.apply(&Transaction::Dispute(TxId(1))), Ok(()));
+
+            assert_eq!(account.state, Some(AccountState {
+                client_id,
+                available: Amount(0_0000),
+                held: Amount(10_0000),
+                locked: false
+            }));
+        }
+
+        //re-disputing a resolved transaction, when not allowed
+        {
+            let mut account = Account::with_config(client_id, EngineConfig { allow_redispute: false, ..EngineConfig::default() });
+
+            assert_eq!(account.apply(&Transaction::Deposit(TxId(1), Amount(10_0000))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Dispute(TxId(1))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Resolve(TxId(1))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Dispute(TxId(1))), Err(RejectionReason::AlreadyResolved));
+
+            assert_eq!(account.state, Some(AccountState {
+                client_id,
+                available: Amount(10_0000),
+                held: Amount(0_0000),
+                locked: false
+            }));
+        }
+
+        //a charged back transaction can't be disputed again, even after an unlock
+        {
+            let mut account = Account::new(client_id);
+
+            assert_eq!(account.apply(&Transaction::Deposit(TxId(1), Amount(10_0000))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Deposit(TxId(2), Amount(5_0000))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Dispute(TxId(1))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Chargeback(TxId(1))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Unlock(TxId(3))), Ok(()));
+            assert_eq!(account.apply(&Transaction::Dispute(TxId(1))), Err(RejectionReason::AlreadyChargedBack));
+            assert_eq!(account.apply(&Transaction::Chargeback(TxId(1))), Err(RejectionReason::NotDisputed));
+
+            assert_eq!(account.state, Some(AccountState {
+                client_id,
+                available: Amount(5_0000),
+                held: Amount(0_0000),
+                locked: false
+            }));
+        }
     }
 
 
@@ -543,7 +1500,7 @@ mod test {
             let records = vec![];
             let expected = vec![];
 
-            let result = run(&records);
+            let result = run(records);
 
             assert_eq!(result, expected);
         }
@@ -551,15 +1508,15 @@ mod test {
         //one client + invalid record
         {
             let records = vec![
-                InputRecord{r#type: "".to_string(), client: 1, tx: 1, amount: None},
-                InputRecord{r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(10.0)},
-                InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 3, amount: Some(2.0)},
+                InputRecord{r#type: "".to_string(), client: 1, tx: 1, amount: None, line: 0, timestamp: None},
+                InputRecord{r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(10_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 3, amount: Some(Amount(2_0000)), line: 0, timestamp: None},
             ];
             let expected = vec![
-                AccountState{client_id: ClientId(1), available: Amount(8.0), held: Amount(0.0), locked: false},
+                AccountState{client_id: ClientId(1), available: Amount(8_0000), held: Amount(0_0000), locked: false},
             ];
 
-            let result = run(&records);
+            let result = run(records);
 
             assert_eq!(result, expected);
         }
@@ -567,20 +1524,20 @@ mod test {
         //three clients + canceled overdrawing withdrawal
         {
             let records = vec![
-                InputRecord{r#type: "deposit".to_string(), client: 1, tx: 616, amount: Some(10.0)},
-                InputRecord{r#type: "deposit".to_string(), client: 2, tx: 525, amount: Some(10.0)},
-                InputRecord{r#type: "deposit".to_string(), client: 3, tx: 434, amount: Some(10.0)},
-                InputRecord{r#type: "withdrawal".to_string(), client: 3, tx: 343, amount: Some(2.0)},
-                InputRecord{r#type: "withdrawal".to_string(), client: 2, tx: 252, amount: Some(8.0)},
-                InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 161, amount: Some(15.0)},
+                InputRecord{r#type: "deposit".to_string(), client: 1, tx: 616, amount: Some(Amount(10_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "deposit".to_string(), client: 2, tx: 525, amount: Some(Amount(10_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "deposit".to_string(), client: 3, tx: 434, amount: Some(Amount(10_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "withdrawal".to_string(), client: 3, tx: 343, amount: Some(Amount(2_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "withdrawal".to_string(), client: 2, tx: 252, amount: Some(Amount(8_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 161, amount: Some(Amount(15_0000)), line: 0, timestamp: None},
             ];
             let expected = vec![
-                AccountState{client_id: ClientId(1), available: Amount(10.0), held: Amount(0.0), locked: false},
-                AccountState{client_id: ClientId(2), available: Amount(2.0), held: Amount(0.0), locked: false},
-                AccountState{client_id: ClientId(3), available: Amount(8.0), held: Amount(0.0), locked: false},
+                AccountState{client_id: ClientId(1), available: Amount(10_0000), held: Amount(0_0000), locked: false},
+                AccountState{client_id: ClientId(2), available: Amount(2_0000), held: Amount(0_0000), locked: false},
+                AccountState{client_id: ClientId(3), available: Amount(8_0000), held: Amount(0_0000), locked: false},
             ];
 
-            let result = run(&records);
+            let result = run(records);
 
             assert_eq!(result, expected);
         }
@@ -588,29 +1545,29 @@ mod test {
         //three clients w/ disputes: pending, resolved, and charged back
         {
             let records = vec![
-                InputRecord{r#type: "deposit".to_string(), client: 1, tx: 616, amount: Some(10.0)},
-                InputRecord{r#type: "deposit".to_string(), client: 2, tx: 525, amount: Some(10.0)},
-                InputRecord{r#type: "deposit".to_string(), client: 3, tx: 434, amount: Some(10.0)},
+                InputRecord{r#type: "deposit".to_string(), client: 1, tx: 616, amount: Some(Amount(10_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "deposit".to_string(), client: 2, tx: 525, amount: Some(Amount(10_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "deposit".to_string(), client: 3, tx: 434, amount: Some(Amount(10_0000)), line: 0, timestamp: None},
 
-                InputRecord{r#type: "dispute".to_string(), client: 1, tx: 616, amount: None},
-                InputRecord{r#type: "dispute".to_string(), client: 2, tx: 525, amount: None},
-                InputRecord{r#type: "dispute".to_string(), client: 3, tx: 434, amount: None},
+                InputRecord{r#type: "dispute".to_string(), client: 1, tx: 616, amount: None, line: 0, timestamp: None},
+                InputRecord{r#type: "dispute".to_string(), client: 2, tx: 525, amount: None, line: 0, timestamp: None},
+                InputRecord{r#type: "dispute".to_string(), client: 3, tx: 434, amount: None, line: 0, timestamp: None},
 
-                InputRecord{r#type: "resolve".to_string(), client: 2, tx: 525, amount: None},
-                InputRecord{r#type: "chargeback".to_string(), client: 3, tx: 434, amount: None},
+                InputRecord{r#type: "resolve".to_string(), client: 2, tx: 525, amount: None, line: 0, timestamp: None},
+                InputRecord{r#type: "chargeback".to_string(), client: 3, tx: 434, amount: None, line: 0, timestamp: None},
 
-                InputRecord{r#type: "withdrawal".to_string(), client: 3, tx: 343, amount: Some(5.0)},
-                InputRecord{r#type: "withdrawal".to_string(), client: 2, tx: 252, amount: Some(5.0)},
-                InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 161, amount: Some(5.0)},
+                InputRecord{r#type: "withdrawal".to_string(), client: 3, tx: 343, amount: Some(Amount(5_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "withdrawal".to_string(), client: 2, tx: 252, amount: Some(Amount(5_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 161, amount: Some(Amount(5_0000)), line: 0, timestamp: None},
             ];
 
             let expected = vec![
-                AccountState{client_id: ClientId(1), available: Amount(0.0), held: Amount(10.0), locked: false},
-                AccountState{client_id: ClientId(2), available: Amount(5.0), held: Amount(0.0), locked: false},
-                AccountState{client_id: ClientId(3), available: Amount(0.0), held: Amount(0.0), locked: true},
+                AccountState{client_id: ClientId(1), available: Amount(0_0000), held: Amount(10_0000), locked: false},
+                AccountState{client_id: ClientId(2), available: Amount(5_0000), held: Amount(0_0000), locked: false},
+                AccountState{client_id: ClientId(3), available: Amount(0_0000), held: Amount(0_0000), locked: true},
             ];
 
-            let result = run(&records);
+            let result = run(records);
 
             assert_eq!(result, expected);
         }
@@ -618,35 +1575,480 @@ mod test {
         //disputes + resolutions: wrong clients/transaction IDs
         {
             let records = vec![
-                InputRecord{r#type: "deposit".to_string(), client: 1, tx: 616, amount: Some(10.0)},
-                InputRecord{r#type: "deposit".to_string(), client: 2, tx: 525, amount: Some(10.0)},
-                InputRecord{r#type: "deposit".to_string(), client: 3, tx: 434, amount: Some(10.0)},
+                InputRecord{r#type: "deposit".to_string(), client: 1, tx: 616, amount: Some(Amount(10_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "deposit".to_string(), client: 2, tx: 525, amount: Some(Amount(10_0000)), line: 0, timestamp: None},
+                InputRecord{r#type: "deposit".to_string(), client: 3, tx: 434, amount: Some(Amount(10_0000)), line: 0, timestamp: None},
 
                 //wrong client
-                InputRecord{r#type: "dispute".to_string(), client: 2, tx: 616, amount: None},
+                InputRecord{r#type: "dispute".to_string(), client: 2, tx: 616, amount: None, line: 0, timestamp: None},
 
                 //non-existent client
-                InputRecord{r#type: "dispute".to_string(), client: 5, tx: 525, amount: None},
+                InputRecord{r#type: "dispute".to_string(), client: 5, tx: 525, amount: None, line: 0, timestamp: None},
 
                 //valid
-                InputRecord{r#type: "dispute".to_string(), client: 3, tx: 434, amount: None},
+                InputRecord{r#type: "dispute".to_string(), client: 3, tx: 434, amount: None, line: 0, timestamp: None},
 
                 //wrong transaction ID
-                InputRecord{r#type: "resolve".to_string(), client: 2, tx: 434, amount: None},
+                InputRecord{r#type: "resolve".to_string(), client: 2, tx: 434, amount: None, line: 0, timestamp: None},
 
                 //wrong client
-                InputRecord{r#type: "chargeback".to_string(), client: 2, tx: 434, amount: None},
+                InputRecord{r#type: "chargeback".to_string(), client: 2, tx: 434, amount: None, line: 0, timestamp: None},
             ];
 
             let expected = vec![
-                AccountState{client_id: ClientId(1), available: Amount(10.0), held: Amount(0.0), locked: false},
-                AccountState{client_id: ClientId(2), available: Amount(10.0), held: Amount(0.0), locked: false},
-                AccountState{client_id: ClientId(3), available: Amount(0.0), held: Amount(10.0), locked: false},
+                AccountState{client_id: ClientId(1), available: Amount(10_0000), held: Amount(0_0000), locked: false},
+                AccountState{client_id: ClientId(2), available: Amount(10_0000), held: Amount(0_0000), locked: false},
+                AccountState{client_id: ClientId(3), available: Amount(0_0000), held: Amount(10_0000), locked: false},
             ];
 
-            let result = run(&records);
+            let result = run(records);
 
             assert_eq!(result, expected);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn try_run_test() {
+
+        //success
+        {
+            let records: Vec<Result<InputRecord, &str>> = vec![
+                Ok(InputRecord{r#type: "deposit".to_string(), client: 1, tx: 1, amount: Some(Amount(10_0000)), line: 0, timestamp: None}),
+                Ok(InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 2, amount: Some(Amount(2_0000)), line: 0, timestamp: None}),
+            ];
+            let expected = vec![
+                AccountState{client_id: ClientId(1), available: Amount(8_0000), held: Amount(0_0000), locked: false},
+            ];
+
+            let result = try_run(records);
+
+            assert_eq!(result, Ok(expected));
+        }
+
+        //failure: the first error is returned
+        {
+            let records = vec![
+                Ok(InputRecord{r#type: "deposit".to_string(), client: 1, tx: 1, amount: Some(Amount(10_0000)), line: 0, timestamp: None}),
+                Err("first"),
+                Err("second"),
+            ];
+
+            let result = try_run(records);
+
+            assert_eq!(result, Err("first"));
+        }
+    }
+
+    #[test]
+    fn run_as_of_test() {
+
+        let records = vec![
+            InputRecord{r#type: "deposit".to_string(), client: 1, tx: 1, amount: Some(Amount(10_0000)), line: 2, timestamp: None},
+            InputRecord{r#type: "deposit".to_string(), client: 2, tx: 2, amount: Some(Amount(5_0000)), line: 3, timestamp: None},
+            InputRecord{r#type: "dispute".to_string(), client: 1, tx: 1, amount: None, line: 4, timestamp: None},
+            InputRecord{r#type: "withdrawal".to_string(), client: 2, tx: 3, amount: Some(Amount(1_0000)), line: 5, timestamp: None},
+            InputRecord{r#type: "chargeback".to_string(), client: 1, tx: 1, amount: None, line: 6, timestamp: None},
+        ];
+
+        //as of a line: just before the dispute
+        {
+            let expected = vec![
+                AccountState{client_id: ClientId(1), available: Amount(10_0000), held: Amount(0_0000), locked: false},
+                AccountState{client_id: ClientId(2), available: Amount(5_0000), held: Amount(0_0000), locked: false},
+            ];
+
+            assert_eq!(run_as_of(records.clone(), AsOf::Line(3)), expected);
+        }
+
+        //as of a line: the dispute, but not the chargeback
+        {
+            let expected = vec![
+                AccountState{client_id: ClientId(1), available: Amount(0_0000), held: Amount(10_0000), locked: false},
+                AccountState{client_id: ClientId(2), available: Amount(4_0000), held: Amount(0_0000), locked: false},
+            ];
+
+            assert_eq!(run_as_of(records.clone(), AsOf::Line(5)), expected);
+        }
+
+        //as of a transaction ID: its first record (the deposit, not the dispute)
+        {
+            let expected = vec![
+                AccountState{client_id: ClientId(1), available: Amount(10_0000), held: Amount(0_0000), locked: false},
+            ];
+
+            assert_eq!(run_as_of(records.clone(), AsOf::Tx(1)), expected);
+        }
+
+        //past the end of the input: every record
+        assert_eq!(run_as_of(records.clone(), AsOf::Line(100)), run(records.clone()));
+        assert_eq!(run_as_of(records.clone(), AsOf::Tx(100)), run(records.clone()));
+
+        //before the first record
+        assert_eq!(run_as_of(records, AsOf::Line(1)), vec![]);
+    }
+
+    #[test]
+    fn timestamp_test() {
+
+        let record = |r#type: &str, client, tx, amount, timestamp: Option<i64>| InputRecord {
+            r#type: r#type.to_string(), client, tx, amount, line: 0, timestamp: timestamp.map(Timestamp),
+        };
+
+        //success: each client's timestamps are checked separately, and equal timestamps are in order
+        {
+            let mut engine = Engine::new();
+
+            assert_eq!(engine.apply(&record("deposit", 1, 1, Some(Amount(10_0000)), Some(100))), Ok(()));
+            assert_eq!(engine.apply(&record("deposit", 2, 2, Some(Amount(10_0000)), Some(50))), Ok(()));
+            assert_eq!(engine.apply(&record("withdrawal", 1, 3, Some(Amount(1_0000)), Some(100))), Ok(()));
+
+            //untimed records aren't checked, and don't change the client's last timestamp
+            assert_eq!(engine.apply(&record("withdrawal", 1, 4, Some(Amount(1_0000)), None)), Ok(()));
+            assert_eq!(engine.apply(&record("withdrawal", 1, 5, Some(Amount(1_0000)), Some(100))), Ok(()));
+        }
+
+        //failure: out of order
+        {
+            let mut engine = Engine::new();
+
+            assert_eq!(engine.apply(&record("deposit", 1, 1, Some(Amount(10_0000)), Some(100))), Ok(()));
+            assert_eq!(engine.apply(&record("withdrawal", 1, 2, Some(Amount(1_0000)), Some(99))).unwrap_err().reason,
+                RejectionReason::OutOfOrder);
+            assert_eq!(engine.apply(&record("dispute", 1, 1, None, Some(0))).unwrap_err().reason, RejectionReason::OutOfOrder);
+
+            //rejected records don't change the client's last timestamp
+            assert_eq!(engine.apply(&record("withdrawal", 1, 3, Some(Amount(100_0000)), Some(200))).unwrap_err().reason,
+                RejectionReason::InsufficientFunds);
+            assert_eq!(engine.apply(&record("withdrawal", 1, 4, Some(Amount(1_0000)), Some(150))), Ok(()));
+
+            assert_eq!(engine.snapshot(), vec![
+                AccountState{client_id: ClientId(1), available: Amount(9_0000), held: Amount(0_0000), locked: false},
+            ]);
+        }
+
+        //as of a cutoff time
+        {
+            let records = vec![
+                record("deposit", 1, 1, Some(Amount(10_0000)), Some(100)),
+                record("deposit", 2, 2, Some(Amount(5_0000)), Some(300)),
+                record("withdrawal", 1, 3, Some(Amount(1_0000)), None),
+                record("withdrawal", 1, 4, Some(Amount(2_0000)), Some(200)),
+            ];
+
+            assert_eq!(run_to_cutoff(records.clone(), Timestamp(200)), vec![
+                AccountState{client_id: ClientId(1), available: Amount(7_0000), held: Amount(0_0000), locked: false},
+            ]);
+            assert_eq!(run_to_cutoff(records.clone(), Timestamp(199)), vec![
+                AccountState{client_id: ClientId(1), available: Amount(9_0000), held: Amount(0_0000), locked: false},
+            ]);
+            assert_eq!(run_to_cutoff(records, Timestamp(99)), vec![]);
+        }
+    }
+
+    #[test]
+    fn time_limit_test() {
+
+        let second = 1_000_000_000;
+        let record = |r#type: &str, tx, amount, seconds: Option<i64>| InputRecord {
+            r#type: r#type.to_string(), client: 1, tx, amount, line: 0, timestamp: seconds.map(|seconds| Timestamp(seconds * second)),
+        };
+        let with_windows = |dispute_window, chargeback_deadline| Engine::with_config(EngineConfig {
+            dispute_window: Some(dispute_window), chargeback_deadline: Some(chargeback_deadline), ..EngineConfig::default()
+        });
+
+        //a time window
+        {
+            let mut engine = with_windows(Window::Time(Duration::from_secs(60)), Window::Time(Duration::from_secs(30)));
+
+            assert_eq!(engine.apply(&record("deposit", 1, Some(Amount(10_0000)), Some(0))), Ok(()));
+            assert_eq!(engine.apply(&record("deposit", 2, Some(Amount(10_0000)), Some(10))), Ok(()));
+
+            //failure: past the window
+            assert_eq!(engine.apply(&record("dispute", 1, None, Some(61))).unwrap_err().reason,
+                RejectionReason::DisputeWindowExpired);
+
+            //success: at the end of the window
+            assert_eq!(engine.apply(&record("dispute", 2, None, Some(70))), Ok(()));
+
+            //failure: past the deadline
+            assert_eq!(engine.apply(&record("chargeback", 2, None, Some(101))).unwrap_err().reason,
+                RejectionReason::ChargebackDeadlinePassed);
+
+            //the dispute stays open after its deadline: it can still be resolved
+            assert_eq!(engine.apply(&record("resolve", 2, None, Some(102))), Ok(()));
+            assert_eq!(engine.snapshot(), vec![
+                AccountState{client_id: ClientId(1), available: Amount(20_0000), held: Amount(0_0000), locked: false},
+            ]);
+        }
+
+        //a time window isn't enforced without timestamps
+        {
+            let mut engine = with_windows(Window::Time(Duration::from_secs(60)), Window::Time(Duration::from_secs(30)));
+
+            assert_eq!(engine.apply(&record("deposit", 1, Some(Amount(10_0000)), None)), Ok(()));
+            assert_eq!(engine.apply(&record("dispute", 1, None, Some(1000))), Ok(()));
+            assert_eq!(engine.apply(&record("chargeback", 1, None, None)), Ok(()));
+        }
+
+        //a transaction count window: counts admitted transactions on any client account
+        {
+            let mut engine = with_windows(Window::Transactions(2), Window::Transactions(1));
+
+            assert_eq!(engine.apply(&record("deposit", 1, Some(Amount(10_0000)), None)), Ok(()));
+            assert_eq!(engine.apply(&InputRecord { client: 2, ..record("deposit", 2, Some(Amount(1_0000)), None) }), Ok(()));
+
+            //success: 2 transactions after the deposit
+            assert_eq!(engine.apply(&record("dispute", 1, None, None)), Ok(()));
+            //success: 1 transaction after the dispute
+            assert_eq!(engine.apply(&record("chargeback", 1, None, None)), Ok(()));
+
+            assert_eq!(engine.apply(&InputRecord { client: 2, ..record("dispute", 2, None, None) }).unwrap_err().reason,
+                RejectionReason::DisputeWindowExpired);
+        }
+
+        //window strings
+        assert_eq!("120d".parse(), Ok(Window::Time(Duration::from_secs(120 * 24 * 60 * 60))));
+        assert_eq!("36h".parse(), Ok(Window::Time(Duration::from_secs(36 * 60 * 60))));
+        assert_eq!("90m".parse(), Ok(Window::Time(Duration::from_secs(90 * 60))));
+        assert_eq!("30s".parse(), Ok(Window::Time(Duration::from_secs(30))));
+        assert_eq!("1000tx".parse(), Ok(Window::Transactions(1000)));
+        for invalid in ["", "d", "120", "-1d", "1.5h", "10w", "tx", "é"] {
+            assert!(invalid.parse::<Window>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn as_of_limit_test() {
+
+        let deposit = |tx, line| Ok(InputRecord{r#type: "deposit".to_string(), client: 1, tx, amount: Some(Amount(1_0000)), line, timestamp: None});
+        let invalid = |line| Err(InputError::UnknownType{line, raw: "bogus,1,1,".to_string()});
+
+        //line: invalid lines count too
+        {
+            let mut limit = AsOfLimit::new(AsOf::Line(3));
+
+            assert!(limit.includes(&deposit(1, 2)));
+            assert!(!limit.is_reached());
+            assert!(limit.includes(&invalid(3)));
+            assert!(limit.is_reached());
+            assert!(!limit.includes(&deposit(2, 4)));
+
+            //every later item is excluded, even if its line number is lower (e.g. in another input file)
+            assert!(!limit.includes(&deposit(3, 2)));
+        }
+
+        //line: a skipped line number
+        {
+            let mut limit = AsOfLimit::new(AsOf::Line(3));
+
+            assert!(limit.includes(&deposit(1, 2)));
+            assert!(!limit.includes(&deposit(2, 4)));
+            assert!(limit.is_reached());
+        }
+
+        //transaction ID: invalid lines don't match it
+        {
+            let mut limit = AsOfLimit::new(AsOf::Tx(2));
+
+            assert!(limit.includes(&invalid(2)));
+            assert!(limit.includes(&deposit(1, 3)));
+            assert!(limit.includes(&deposit(2, 4)));
+            assert!(limit.is_reached());
+            assert!(!limit.includes(&deposit(3, 5)));
+        }
+    }
+
+    #[test]
+    fn engine_test() {
+
+        let mut engine = Engine::new();
+
+        //no transactions
+        assert_eq!(engine.snapshot(), vec![]);
+
+        //a non-deposit doesn't open an account
+        let result = engine.apply(&InputRecord{r#type: "withdrawal".to_string(), client: 2, tx: 1, amount: Some(Amount(1_0000)), line: 2, timestamp: None});
+        assert_eq!(result, Err(Rejection{line: 2, client: 2, tx: 1, reason: RejectionReason::AccountNotOpen}));
+        assert_eq!(engine.snapshot(), vec![]);
+        assert_eq!(engine.account_state(ClientId(2)), None);
+
+        //state is updated after each transaction
+        let result = engine.apply(&InputRecord{r#type: "deposit".to_string(), client: 2, tx: 2, amount: Some(Amount(10_0000)), line: 3, timestamp: None});
+        assert_eq!(result, Ok(()));
+        assert_eq!(engine.snapshot(), vec![
+            AccountState{client_id: ClientId(2), available: Amount(10_0000), held: Amount(0_0000), locked: false},
+        ]);
+        assert_eq!(engine.account_state(ClientId(2)),
+            Some(AccountState{client_id: ClientId(2), available: Amount(10_0000), held: Amount(0_0000), locked: false}));
+        assert_eq!(engine.dispute_status(2),
+            Some(DisputeStatus{client: 2, tx: 2, r#type: "deposit", amount: Amount(10_0000), state: DisputeState::Settled}));
+        assert_eq!(engine.dispute_status(1), None);
+
+        assert_eq!(engine.apply(&InputRecord{r#type: "deposit".to_string(), client: 1, tx: 3, amount: Some(Amount(5_0000)), line: 4, timestamp: None}), Ok(()));
+        assert_eq!(engine.apply(&InputRecord{r#type: "dispute".to_string(), client: 2, tx: 2, amount: None, line: 5, timestamp: None}), Ok(()));
+        assert_eq!(engine.dispute_status(2).map(|status| status.state), Some(DisputeState::Disputed));
+        assert_eq!(engine.snapshot(), vec![
+            AccountState{client_id: ClientId(1), available: Amount(5_0000), held: Amount(0_0000), locked: false},
+            AccountState{client_id: ClientId(2), available: Amount(0_0000), held: Amount(10_0000), locked: false},
+        ]);
+
+        //a locked account rejects further transactions
+        assert_eq!(engine.apply(&InputRecord{r#type: "chargeback".to_string(), client: 2, tx: 2, amount: None, line: 6, timestamp: None}), Ok(()));
+        let result = engine.apply(&InputRecord{r#type: "deposit".to_string(), client: 2, tx: 4, amount: Some(Amount(1_0000)), line: 7, timestamp: None});
+        assert_eq!(result, Err(Rejection{line: 7, client: 2, tx: 4, reason: RejectionReason::AccountLocked}));
+        assert_eq!(engine.snapshot(), vec![
+            AccountState{client_id: ClientId(1), available: Amount(5_0000), held: Amount(0_0000), locked: false},
+            AccountState{client_id: ClientId(2), available: Amount(0_0000), held: Amount(0_0000), locked: true},
+        ]);
+
+        //an invalid record is rejected, with the kind of input error
+        let result = engine.apply(&InputRecord{r#type: "no_such_tx_type".to_string(), client: 1, tx: 5, amount: None, line: 8, timestamp: None});
+        assert_eq!(result, Err(Rejection{line: 8, client: 1, tx: 5, reason: RejectionReason::InvalidRecord(InputErrorKind::UnknownType)}));
+        assert_eq!(RejectionReason::InvalidRecord(InputErrorKind::UnknownType).to_string(), "UnknownType");
+    }
+
+    #[test]
+    fn open_disputes_test() {
+
+        let record = |r#type: &str, client, tx, amount, timestamp: Option<i64>| InputRecord {
+            r#type: r#type.to_string(), client, tx, amount, line: 0, timestamp: timestamp.map(Timestamp),
+        };
+
+        let mut engine = Engine::new();
+
+        //no disputes
+        assert_eq!(engine.open_disputes(), vec![]);
+
+        for record in [
+            record("deposit", 2, 1, Some(Amount(10_0000)), Some(100)),
+            record("deposit", 1, 2, Some(Amount(5_0000)), None),
+            record("withdrawal", 2, 3, Some(Amount(2_0000)), Some(200)),
+            record("deposit", 2, 4, Some(Amount(1_0000)), Some(300)),
+            record("dispute", 2, 3, None, Some(400)),
+            record("dispute", 1, 2, None, None),
+            record("dispute", 2, 1, None, Some(500)),
+            record("dispute", 2, 4, None, Some(600)),
+            record("resolve", 2, 4, None, Some(700)),
+        ] {
+            assert_eq!(engine.apply(&record), Ok(()));
+        }
+
+        //resolved and charged back disputes aren't open
+        assert_eq!(engine.apply(&record("deposit", 3, 5, Some(Amount(1_0000)), None)), Ok(()));
+        assert_eq!(engine.apply(&record("dispute", 3, 5, None, None)), Ok(()));
+        assert_eq!(engine.apply(&record("chargeback", 3, 5, None, None)), Ok(()));
+
+        //ordered by client ID and transaction ID
+        assert_eq!(engine.open_disputes(), vec![
+            OpenDispute{client: 1, tx: 2, r#type: "deposit", amount: Amount(5_0000), opened_at: None, sequence: Some(6)},
+            OpenDispute{client: 2, tx: 1, r#type: "deposit", amount: Amount(10_0000), opened_at: Some(Timestamp(500)), sequence: Some(7)},
+            OpenDispute{client: 2, tx: 3, r#type: "withdrawal", amount: Amount(2_0000), opened_at: Some(Timestamp(400)), sequence: Some(5)},
+        ]);
+    }
+
+    #[test]
+    fn account_rejection_test() {
+
+        let mut account = Account::new(ClientId(1));
+
+        //the account isn't open yet
+        assert_eq!(account.apply(&Transaction::Withdrawal(TxId(1), Amount(1_0000))), Err(RejectionReason::AccountNotOpen));
+        assert_eq!(account.apply(&Transaction::Dispute(TxId(1))), Err(RejectionReason::AccountNotOpen));
+
+        assert_eq!(account.apply(&Transaction::Deposit(TxId(2), Amount(10_0000))), Ok(()));
+
+        //overdrawing withdrawal
+        assert_eq!(account.apply(&Transaction::Withdrawal(TxId(3), Amount(10_0001))), Err(RejectionReason::InsufficientFunds));
+
+        //references to nonexistent deposits
+        assert_eq!(account.apply(&Transaction::Dispute(TxId(3))), Err(RejectionReason::UnknownTx));
+        assert_eq!(account.apply(&Transaction::Resolve(TxId(3))), Err(RejectionReason::UnknownTx));
+        assert_eq!(account.apply(&Transaction::Chargeback(TxId(3))), Err(RejectionReason::UnknownTx));
+
+        //references to an undisputed deposit
+        assert_eq!(account.apply(&Transaction::Resolve(TxId(2))), Err(RejectionReason::NotDisputed));
+        assert_eq!(account.apply(&Transaction::Chargeback(TxId(2))), Err(RejectionReason::NotDisputed));
+
+        //the account is locked by a chargeback
+        assert_eq!(account.apply(&Transaction::Dispute(TxId(2))), Ok(()));
+        assert_eq!(account.apply(&Transaction::Chargeback(TxId(2))), Ok(()));
+        assert_eq!(account.apply(&Transaction::Deposit(TxId(4), Amount(1_0000))), Err(RejectionReason::AccountLocked));
+
+        //rejected transactions had no effect
+        assert_eq!(account.state, Some(AccountState {
+            client_id: ClientId(1),
+            available: Amount(0_0000),
+            held: Amount(0_0000),
+            locked: true
+        }));
+    }
+
+    #[test]
+    fn overflow_test() {
+
+        let mut account = Account::new(ClientId(1));
+
+        //available funds would overflow
+        assert_eq!(account.apply(&Transaction::Deposit(TxId(1), Amount(i64::MAX - 1))), Ok(()));
+        assert_eq!(account.apply(&Transaction::Deposit(TxId(2), Amount(2))), Err(RejectionReason::Overflow));
+
+        //the rejected deposit wasn't recorded
+        assert_eq!(account.apply(&Transaction::Dispute(TxId(2))), Err(RejectionReason::UnknownTx));
+
+        //total funds would overflow: holding a disputed withdrawal's funds
+        assert_eq!(account.apply(&Transaction::Withdrawal(TxId(3), Amount(5))), Ok(()));
+        assert_eq!(account.apply(&Transaction::Deposit(TxId(4), Amount(5))), Ok(()));
+        assert_eq!(account.apply(&Transaction::Dispute(TxId(3))), Err(RejectionReason::Overflow));
+
+        //the rejected dispute didn't open
+        assert_eq!(account.apply(&Transaction::Resolve(TxId(3))), Err(RejectionReason::NotDisputed));
+
+        //rejected transactions had no effect
+        assert_eq!(account.state, Some(AccountState {
+            client_id: ClientId(1),
+            available: Amount(i64::MAX - 1),
+            held: Amount(0_0000),
+            locked: false
+        }));
+    }
+
+    #[test]
+    fn duplicate_tx_test() {
+
+        let mut engine = Engine::new();
+
+        assert_eq!(engine.apply(&InputRecord{r#type: "deposit".to_string(), client: 1, tx: 1, amount: Some(Amount(10_0000)), line: 2, timestamp: None}), Ok(()));
+        assert_eq!(engine.apply(&InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 2, amount: Some(Amount(1_0000)), line: 3, timestamp: None}), Ok(()));
+
+        //a repeated deposit on the same client account
+        let result = engine.apply(&InputRecord{r#type: "deposit".to_string(), client: 1, tx: 1, amount: Some(Amount(10_0000)), line: 4, timestamp: None});
+        assert_eq!(result, Err(Rejection{line: 4, client: 1, tx: 1, reason: RejectionReason::DuplicateTx}));
+
+        //a deposit on another client account, reusing a withdrawal's ID
+        let result = engine.apply(&InputRecord{r#type: "deposit".to_string(), client: 2, tx: 2, amount: Some(Amount(5_0000)), line: 5, timestamp: None});
+        assert_eq!(result, Err(Rejection{line: 5, client: 2, tx: 2, reason: RejectionReason::DuplicateTx}));
+
+        //a rejected withdrawal's ID is still used
+        let result = engine.apply(&InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 3, amount: Some(Amount(100_0000)), line: 6, timestamp: None});
+        assert_eq!(result, Err(Rejection{line: 6, client: 1, tx: 3, reason: RejectionReason::InsufficientFunds}));
+        let result = engine.apply(&InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 3, amount: Some(Amount(1_0000)), line: 7, timestamp: None});
+        assert_eq!(result, Err(Rejection{line: 7, client: 1, tx: 3, reason: RejectionReason::DuplicateTx}));
+
+        //disputes, resolves, and chargebacks reference existing IDs: not duplicates
+        assert_eq!(engine.apply(&InputRecord{r#type: "dispute".to_string(), client: 1, tx: 1, amount: None, line: 8, timestamp: None}), Ok(()));
+        assert_eq!(engine.apply(&InputRecord{r#type: "resolve".to_string(), client: 1, tx: 1, amount: None, line: 9, timestamp: None}), Ok(()));
+
+        //admin actions have their own IDs
+        let result = engine.apply(&InputRecord{r#type: "freeze".to_string(), client: 1, tx: 2, amount: None, line: 10, timestamp: None});
+        assert_eq!(result, Err(Rejection{line: 10, client: 1, tx: 2, reason: RejectionReason::DuplicateTx}));
+        assert_eq!(engine.apply(&InputRecord{r#type: "freeze".to_string(), client: 1, tx: 4, amount: None, line: 11, timestamp: None}), Ok(()));
+        let result = engine.apply(&InputRecord{r#type: "unlock".to_string(), client: 1, tx: 4, amount: None, line: 12, timestamp: None});
+        assert_eq!(result, Err(Rejection{line: 12, client: 1, tx: 4, reason: RejectionReason::DuplicateTx}));
+        assert_eq!(engine.apply(&InputRecord{r#type: "unlock".to_string(), client: 1, tx: 5, amount: None, line: 13, timestamp: None}), Ok(()));
+
+        //the duplicates had no effect
+        assert_eq!(engine.snapshot(), vec![
+            AccountState{client_id: ClientId(1), available: Amount(9_0000), held: Amount(0_0000), locked: false},
+        ]);
+    }
+}
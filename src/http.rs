@@ -0,0 +1,283 @@
+//! An HTTP/JSON API server (with the `http` feature): applies transactions to a shared engine.
+//!
+//! Endpoints:
+//! * `POST /transactions`: applies a transaction (a JSON object, see `input::parse_json`),
+//!   or a batch of transactions (a JSON array of objects), in order.
+//!   Each transaction's result is `{"result": "ok"}`, `{"result": "rejected", "reason": "<reason>"}`,
+//!   or `{"result": "error", "error": "<description>"}` (e.g. for an invalid transaction);
+//!   a batch's results are an array, in the same order. A transaction that can't be journaled is answered with
+//!   status 500, and stops its batch: the batch's results end with that transaction's, and the rest aren't applied.
+//! * `GET /accounts`: every open account's state (see `account_state::OutputRecord`), ordered by client ID
+//! * `GET /accounts/<client>`: a client's account state
+//! * `GET /transactions/<tx>/dispute`: a deposit or withdrawal's dispute status (see `engine::DisputeStatus`)
+//!
+//! Errors are answered with an error status code and `{"error": "<description>"}`.
+//!
+//! Requests are handled one at a time, in the order they're received, so each one sees the results of the ones before it.
+
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response};
+
+use crate::account_state::OutputRecord;
+use crate::engine::{ClientId,Engine};
+use crate::input::parse_json;
+use crate::journal::Journal;
+use crate::server::Shared;
+
+
+/// An HTTP server bound to a TCP address
+pub struct HttpServer {
+    server: tiny_http::Server,
+    shared: Mutex<Shared>,
+}
+
+impl HttpServer {
+
+    /// Binds to `address`, serving requests with `engine` (and journaling to `journal`, if present)
+    pub fn bind(address: &str, engine: Engine, journal: Option<Journal>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+
+        let server = tiny_http::Server::http(address)?;
+
+        Ok(Self { server, shared: Mutex::new(Shared { engine, journal }) })
+    }
+
+    /// The address the server is bound to (e.g. to find the port chosen for port 0)
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.server.server_addr().to_ip()
+    }
+
+    /// Answers requests, one at a time.
+    /// A response that can't be sent (e.g. to a client that reset its connection) only fails that request.
+    pub fn run(&self) {
+
+        for mut request in self.server.incoming_requests() {
+
+            let mut body = String::new();
+            let (status, json) = match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => self.handle(request.method(), request.url(), &body),
+                Err(e) => error(400, format!("unreadable request body: {}", e)),
+            };
+
+            let content_type = Header::from_bytes("Content-Type", "application/json").expect("valid header");
+
+            if let Err(e) = request.respond(Response::from_string(json.to_string())
+                .with_status_code(status)
+                .with_header(content_type)) {
+                eprintln!("couldn't send a response: {}", e);
+            }
+        }
+    }
+
+    /// The response status code and JSON for a request
+    fn handle(&self, method: &Method, url: &str, body: &str) -> (u16, Value) {
+
+        let path = url.split('?').next().unwrap_or("");
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+
+        let mut shared = self.shared.lock().expect("engine lock poisoned");
+
+        match (method, segments.as_slice()) {
+
+            (Method::Post, ["transactions"]) => {
+                let json: Value = match serde_json::from_str(body) {
+                    Ok(json) => json,
+                    Err(e) => return error(400, format!("invalid JSON: {}", e)),
+                };
+
+                match json {
+                    //transactions in a batch are numbered from 1, for error descriptions
+                    Value::Array(transactions) => {
+                        let mut results = Vec::with_capacity(transactions.len());
+
+                        for (line, transaction) in (1..).zip(&transactions) {
+                            let (status, result) = apply(&mut shared, transaction, line);
+                            results.push(result);
+
+                            //a journal error: later transactions might depend on this one
+                            if status == 500 {
+                                return (500, Value::Array(results));
+                            }
+                        }
+
+                        (200, Value::Array(results))
+                    },
+                    transaction => apply(&mut shared, &transaction, 1),
+                }
+            },
+
+            (Method::Get, ["accounts"]) => {
+                let accounts: Vec<OutputRecord> = shared.engine.snapshot().iter().map(OutputRecord::from).collect();
+                (200, json!(accounts))
+            },
+
+            (Method::Get, ["accounts", client]) => {
+                let client_id = match client.parse() {
+                    Ok(client) => ClientId(client),
+                    Err(_) => return error(400, format!("invalid client ID: {}", client)),
+                };
+
+                match shared.engine.account_state(client_id) {
+                    Some(account_state) => (200, json!(OutputRecord::from(&account_state))),
+                    None => error(404, format!("no open account for client {}", client)),
+                }
+            },
+
+            (Method::Get, ["transactions", tx, "dispute"]) => {
+                let tx = match tx.parse() {
+                    Ok(tx) => tx,
+                    Err(_) => return error(400, format!("invalid transaction ID: {}", tx)),
+                };
+
+                match shared.engine.dispute_status(tx) {
+                    Some(dispute_status) => (200, json!(dispute_status)),
+                    None => error(404, format!("no deposit or withdrawal with transaction ID {}", tx)),
+                }
+            },
+
+            (_, ["transactions"]) | (_, ["accounts"]) | (_, ["accounts", _]) | (_, ["transactions", _, "dispute"]) => {
+                error(405, format!("method not allowed: {}", method))
+            },
+
+            _ => error(404, format!("not found: {}", path)),
+        }
+    }
+}
+
+/// An error response
+fn error(status: u16, description: String) -> (u16, Value) {
+    (status, json!({"error": description}))
+}
+
+/// Applies a JSON transaction: returns its response status code (500 if it can't be journaled) and result
+fn apply(shared: &mut Shared, transaction: &Value, line: u64) -> (u16, Value) {
+
+    let record = match parse_json(transaction, line) {
+        Ok(record) => record,
+        Err(e) => return (400, json!({"result": "error", "error": e.to_string()})),
+    };
+
+    match shared.apply(&record) {
+        Ok(Ok(())) => (200, json!({"result": "ok"})),
+        Ok(Err(rejection)) => (200, json!({"result": "rejected", "reason": rejection.reason})),
+        Err(e) => (500, json!({"result": "error", "error": e.to_string()})),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    #[test]
+    fn handle_test() {
+
+        let server = HttpServer::bind("127.0.0.1:0", Engine::new(), None).unwrap();
+        let post = |body: &str| server.handle(&Method::Post, "/transactions", body);
+        let get = |url: &str| server.handle(&Method::Get, url, "");
+
+        //a transaction
+        assert_eq!(post(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "10.0"}"#), (200, json!({"result": "ok"})));
+
+        //a batch
+        assert_eq!(post(r#"[
+            {"type": "deposit", "client": 2, "tx": 2, "amount": 5},
+            {"type": "withdrawal", "client": 1, "tx": 3, "amount": "2.5"},
+            {"type": "withdrawal", "client": 2, "tx": 4, "amount": "100"},
+            {"type": "deposit", "client": "x", "tx": 5, "amount": "1"},
+            {"type": "dispute", "client": 1, "tx": 1}
+        ]"#), (200, json!([
+            {"result": "ok"},
+            {"result": "ok"},
+            {"result": "rejected", "reason": "InsufficientFunds"},
+            {"result": "error", "error": r#"line 4: invalid client ID: {"amount":"1","client":"x","tx":5,"type":"deposit"}"#},
+            {"result": "ok"},
+        ])));
+
+        //accounts
+        assert_eq!(get("/accounts"), (200, json!([
+            {"client": 1, "available": "-2.5000", "held": "10.0000", "total": "7.5000", "locked": false},
+            {"client": 2, "available": "5.0000", "held": "0.0000", "total": "5.0000", "locked": false},
+        ])));
+        assert_eq!(get("/accounts/2"), (200,
+            json!({"client": 2, "available": "5.0000", "held": "0.0000", "total": "5.0000", "locked": false})));
+
+        //dispute status
+        assert_eq!(get("/transactions/1/dispute"), (200,
+            json!({"client": 1, "tx": 1, "type": "deposit", "amount": "10.0000", "state": "Disputed"})));
+        assert_eq!(get("/transactions/3/dispute"), (200,
+            json!({"client": 1, "tx": 3, "type": "withdrawal", "amount": "2.5000", "state": "Settled"})));
+
+        //failure: invalid transactions
+        assert_eq!(post(r#"{"type": "deposit", "client": 1}"#).0, 400);
+        assert_eq!(post("not json").0, 400);
+
+        //failure: a batch stops at a transaction that can't be journaled
+        {
+            let path = std::env::temp_dir().join("rust2021_6_21_http_journal.bin");
+            let _ = std::fs::remove_file(&path);
+
+            let mut engine = Engine::new();
+            let mut journal = Journal::open(&path, &mut engine).unwrap();
+            journal.fail();
+
+            let server = HttpServer::bind("127.0.0.1:0", engine, Some(journal)).unwrap();
+            let (status, results) = server.handle(&Method::Post, "/transactions", r#"[
+                {"type": "deposit", "client": "x", "tx": 1, "amount": "1"},
+                {"type": "deposit", "client": 1, "tx": 2, "amount": "1"},
+                {"type": "deposit", "client": 1, "tx": 3, "amount": "1"}
+            ]"#);
+
+            assert_eq!(status, 500);
+            assert_eq!(results.as_array().unwrap().len(), 2);
+            assert_eq!(results[1], json!({"result": "error", "error": "journal can't be appended to after a failed write"}));
+            assert_eq!(server.handle(&Method::Get, "/accounts", ""), (200, json!([])));
+        }
+
+        //failure: not found
+        assert_eq!(get("/accounts/3").0, 404);
+        assert_eq!(get("/transactions/4/dispute").0, 404);
+        assert_eq!(get("/no/such/path").0, 404);
+
+        //failure: invalid IDs
+        assert_eq!(get("/accounts/x").0, 400);
+        assert_eq!(get("/transactions/x/dispute").0, 400);
+
+        //failure: wrong method
+        assert_eq!(get("/transactions").0, 405);
+        assert_eq!(server.handle(&Method::Post, "/accounts", "").0, 405);
+    }
+
+    #[test]
+    fn run_test() {
+
+        let server = HttpServer::bind("127.0.0.1:0", Engine::new(), None).unwrap();
+        let address = server.local_addr().unwrap();
+        std::thread::spawn(move || server.run());
+
+        let request = |request: &str| {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let body = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.5"}"#;
+        let response = request(&format!(
+            "POST /transactions HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}", body.len(), body));
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with(r#"{"result":"ok"}"#));
+
+        let response = request("GET /accounts/1 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("Content-Type: application/json"));
+        assert!(response.ends_with(r#"{"available":"1.5000","client":1,"held":"0.0000","locked":false,"total":"1.5000"}"#));
+    }
+}
@@ -1,37 +1,528 @@
-//! Processes input CSVs
+//! Processes input CSVs (and JSON transaction records)
 
 use std::error::Error;
-use csv::{ReaderBuilder,Trim};
-use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+use csv::{Reader,ReaderBuilder,StringRecord};
+use serde_json::Value;
 
+use crate::amount::Amount;
+use crate::timestamp::Timestamp;
 
-#[derive(Clone, PartialEq, PartialOrd, Debug, Deserialize)]
+
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
 ///A typed representation of a single input line
 pub struct InputRecord {
     pub r#type: String,
     pub client: u16,
     pub tx: u32,
-    pub amount: Option<f32>,
+    pub amount: Option<Amount>,
+    ///the record's line number in its input (not a CSV column)
+    pub line: u64,
+    ///when the transaction happened, if the input has a timestamp column
+    pub timestamp: Option<Timestamp>,
 }
 
-///Parses a CSV string into InputRecords
-pub fn parse_csv(input_csv: String) -> Result<Vec<InputRecord>, Box<dyn Error>> {
+impl InputRecord {
+
+    ///Whether the record is timestamped after a cutoff time (an untimed record isn't)
+    pub fn is_after(&self, cutoff: Timestamp) -> bool {
+        self.timestamp.is_some_and(|timestamp| timestamp > cutoff)
+    }
+}
+
+impl InputRecord {
+
+    ///Whether the record is an admin action (`unlock` or `freeze`) rather than a client transaction
+    pub fn is_admin_action(&self) -> bool {
+        matches!(self.r#type.as_str(), "unlock" | "freeze")
+    }
+}
+
+impl Display for InputRecord {
+
+    ///Writes the record as a CSV line: `type,client,tx,amount` (then `,timestamp`, if it has one)
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+
+        write!(f, "{},{},{},", self.r#type, self.client, self.tx)?;
+
+        if let Some(amount) = self.amount {
+            write!(f, "{}", amount)?;
+        }
+
+        match self.timestamp {
+            Some(timestamp) => write!(f, ",{}", timestamp),
+            None => Ok(()),
+        }
+    }
+}
+
+///An invalid input line: each variant has the line's number and raw text
+/// (and, if they were parsed before the error, its client and transaction IDs)
+#[derive(Clone, PartialEq, Debug)]
+pub enum InputError {
+    ///The header line doesn't name the required columns
+    BadHeader { line: u64, raw: String },
+    ///The line has too few fields for a transaction record
+    TruncatedLine { line: u64, raw: String },
+    ///The transaction type isn't recognized
+    UnknownType { line: u64, raw: String },
+    ///A deposit or withdrawal has no amount
+    MissingAmount { line: u64, raw: String },
+    ///A dispute, resolve, chargeback, unlock, or freeze has an amount
+    UnexpectedAmount { line: u64, raw: String },
+    ///A deposit or withdrawal has an amount that's zero or negative
+    NonPositiveAmount { line: u64, raw: String },
+    ///The client ID isn't a valid u16
+    BadClientId { line: u64, raw: String },
+    ///The transaction ID isn't a valid u32
+    BadTxId { line: u64, raw: String },
+    ///The amount isn't a decimal number with at most 4 places past the decimal
+    BadAmount { line: u64, raw: String, client: u16, tx: u32 },
+    ///The timestamp isn't an RFC 3339 date and time, or a number of seconds since the Unix epoch
+    BadTimestamp { line: u64, raw: String, client: u16, tx: u32 },
+    ///The line couldn't be read at all (I/O failure, invalid UTF-8, etc)
+    Unreadable { line: u64, raw: String },
+}
+
+///The kind of an InputError, without its line
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum InputErrorKind {
+    BadHeader,
+    TruncatedLine,
+    UnknownType,
+    MissingAmount,
+    UnexpectedAmount,
+    NonPositiveAmount,
+    BadClientId,
+    BadTxId,
+    BadAmount,
+    BadTimestamp,
+    Unreadable,
+}
+
+impl InputErrorKind {
+
+    ///A short description of this kind of error
+    pub fn description(&self) -> &'static str {
+
+        match self {
+            InputErrorKind::BadHeader => "invalid header",
+            InputErrorKind::TruncatedLine => "truncated line",
+            InputErrorKind::UnknownType => "unknown transaction type",
+            InputErrorKind::MissingAmount => "missing amount",
+            InputErrorKind::UnexpectedAmount => "unexpected amount",
+            InputErrorKind::NonPositiveAmount => "non-positive amount",
+            InputErrorKind::BadClientId => "invalid client ID",
+            InputErrorKind::BadTxId => "invalid transaction ID",
+            InputErrorKind::BadAmount => "invalid amount",
+            InputErrorKind::BadTimestamp => "invalid timestamp",
+            InputErrorKind::Unreadable => "unreadable line",
+        }
+    }
+}
+
+impl InputError {
+
+    ///The kind of error
+    pub fn kind(&self) -> InputErrorKind {
+        self.parts().0
+    }
+
+    ///The line number of the invalid line
+    pub fn line(&self) -> u64 {
+        self.parts().1
+    }
+
+    ///The raw text of the invalid line
+    /// (for `Unreadable` lines, a description of the read error)
+    pub fn raw(&self) -> &str {
+        self.parts().2
+    }
+
+    ///The client and transaction IDs of the invalid line, if they were parsed before the error
+    pub fn ids(&self) -> Option<(u16, u32)> {
+
+        match *self {
+            InputError::BadAmount { client, tx, .. } | InputError::BadTimestamp { client, tx, .. } => Some((client, tx)),
+            _ => None,
+        }
+    }
+
+    ///This error's kind, line number, and raw text
+    fn parts(&self) -> (InputErrorKind, u64, &str) {
+
+        match self {
+            InputError::BadHeader { line, raw } => (InputErrorKind::BadHeader, *line, raw),
+            InputError::TruncatedLine { line, raw } => (InputErrorKind::TruncatedLine, *line, raw),
+            InputError::UnknownType { line, raw } => (InputErrorKind::UnknownType, *line, raw),
+            InputError::MissingAmount { line, raw } => (InputErrorKind::MissingAmount, *line, raw),
+            InputError::UnexpectedAmount { line, raw } => (InputErrorKind::UnexpectedAmount, *line, raw),
+            InputError::NonPositiveAmount { line, raw } => (InputErrorKind::NonPositiveAmount, *line, raw),
+            InputError::BadClientId { line, raw } => (InputErrorKind::BadClientId, *line, raw),
+            InputError::BadTxId { line, raw } => (InputErrorKind::BadTxId, *line, raw),
+            InputError::BadAmount { line, raw, .. } => (InputErrorKind::BadAmount, *line, raw),
+            InputError::BadTimestamp { line, raw, .. } => (InputErrorKind::BadTimestamp, *line, raw),
+            InputError::Unreadable { line, raw } => (InputErrorKind::Unreadable, *line, raw),
+        }
+    }
+}
+
+impl Display for InputError {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+
+        let (kind, line, raw) = self.parts();
+        write!(f, "line {}: {}: {}", line, kind.description(), raw)
+    }
+}
+
+impl Error for InputError {}
+
+///How invalid input lines are handled
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ParsePolicy {
+    ///Abort on the first invalid line
+    Strict,
+    ///Skip every invalid line
+    Lenient,
+    ///Skip up to N invalid lines, then abort on the next one
+    Threshold(usize),
+}
+
+impl ParsePolicy {
+
+    ///Whether processing can continue after the specified number of invalid lines
+    pub fn allows(&self, invalid_lines: usize) -> bool {
+
+        match *self {
+            ParsePolicy::Strict => invalid_lines == 0,
+            ParsePolicy::Lenient => true,
+            ParsePolicy::Threshold(n) => invalid_lines <= n,
+        }
+    }
+}
+
+impl Default for ParsePolicy {
+
+    ///Invalid lines are skipped by default
+    fn default() -> Self {
+        ParsePolicy::Lenient
+    }
+}
+
+impl FromStr for ParsePolicy {
+    type Err = String;
+
+    ///Parses `strict`, `lenient`, or `threshold:N`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        match s {
+            "strict" => Ok(ParsePolicy::Strict),
+            "lenient" => Ok(ParsePolicy::Lenient),
+            _ => {
+                s.strip_prefix("threshold:")
+                    .and_then(|n| n.parse().ok())
+                    .map(ParsePolicy::Threshold)
+                    .ok_or_else(|| format!("invalid parse policy: {}", s))
+            },
+        }
+    }
+}
+
+///The positions of the input columns, as named by the header line
+#[derive(Debug)]
+struct Columns {
+    r#type: usize,
+    client: usize,
+    tx: usize,
+    amount: Option<usize>,
+    timestamp: Option<usize>,
+}
+
+impl Columns {
+
+    ///Finds the columns in a header line (names are trimmed)
+    fn from_headers(headers: &StringRecord) -> Option<Self> {
+
+        let find = |name: &str| headers.iter().position(|header| header.trim() == name);
+
+        Some(Columns {
+            r#type: find("type")?,
+            client: find("client")?,
+            tx: find("tx")?,
+            amount: find("amount"),
+            timestamp: find("timestamp"),
+        })
+    }
+}
+
+///Lazily parses CSV input from a reader into InputRecords:
+/// records are read one at a time, as the returned iterator is advanced
+pub fn read_csv<R: Read>(input: R) -> CsvRecords<R> {
+
+    //fields are trimmed during parsing, so raw line text is available for errors
+    let reader = ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(input);
+
+    CsvRecords { reader, columns: None, string_record: StringRecord::new() }
+}
+
+///An iterator over the InputRecords in CSV input (see `read_csv`)
+pub struct CsvRecords<R> {
+    reader: Reader<R>,
+    //found from the header line, before the first record
+    columns: Option<Columns>,
+    //reused for each record, to avoid reallocation
+    string_record: StringRecord,
+}
+
+impl<R: Read> CsvRecords<R> {
+
+    ///Reads the next record, or None at the end of the input
+    fn read_next(&mut self) -> Result<Option<InputRecord>, InputError> {
+
+        let unreadable = |e: csv::Error| InputError::Unreadable {
+            line: e.position().map_or(0, |position| position.line()),
+            raw: e.to_string(),
+        };
+
+        if !self.reader.read_record(&mut self.string_record).map_err(unreadable)? {
+            return Ok(None);
+        }
+
+        //the header line is read before the first record
+        let columns = match self.columns {
+            Some(ref columns) => columns,
+            None => {
+                let headers = self.reader.headers().map_err(unreadable)?;
+
+                let columns = Columns::from_headers(headers).ok_or_else(|| InputError::BadHeader {
+                    line: 1,
+                    raw: headers.iter().collect::<Vec<_>>().join(","),
+                })?;
+
+                self.columns.get_or_insert(columns)
+            },
+        };
+
+        let line = self.string_record.position().map_or(0, |position| position.line());
+
+        parse_string_record(&self.string_record, columns, line).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for CsvRecords<R> {
+    type Item = Result<InputRecord, InputError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_next().transpose()
+    }
+}
+
+///Parses one CSV line's fields into an InputRecord
+fn parse_string_record(string_record: &StringRecord, columns: &Columns, line: u64) -> Result<InputRecord, InputError> {
+
+    let field = |index: usize| string_record.get(index).map(str::trim);
+
+    parse_fields(
+        [field(columns.r#type), field(columns.client), field(columns.tx), columns.amount.and_then(field), columns.timestamp.and_then(field)],
+        line,
+        || string_record.iter().collect::<Vec<_>>().join(","))
+}
+
+///Parses a record's (trimmed) field texts: `[type, client, tx, amount, timestamp]`.
+/// Every input format is parsed with this, so their errors are identical.
+fn parse_fields<F: Fn() -> String>(fields: [Option<&str>; 5], line: u64, raw: F) -> Result<InputRecord, InputError> {
+
+    let (r#type, client, tx) = match fields {
+        [Some(r#type), Some(client), Some(tx), ..] => (r#type, client, tx),
+        _ => return Err(InputError::TruncatedLine { line, raw: raw() }),
+    };
+
+    let client = client.parse().map_err(|_| InputError::BadClientId { line, raw: raw() })?;
+    let tx = tx.parse().map_err(|_| InputError::BadTxId { line, raw: raw() })?;
+
+    //a missing or empty amount field is no amount
+    let amount = match fields[3] {
+        Some(amount) if !amount.is_empty() => {
+            Some(amount.parse().map_err(|_| InputError::BadAmount { line, raw: raw(), client, tx })?)
+        },
+        _ => None,
+    };
+
+    //likewise for the timestamp
+    let timestamp = match fields[4] {
+        Some(timestamp) if !timestamp.is_empty() => {
+            Some(timestamp.parse().map_err(|_| InputError::BadTimestamp { line, raw: raw(), client, tx })?)
+        },
+        _ => None,
+    };
+
+    Ok(InputRecord { r#type: r#type.to_string(), client, tx, amount, line, timestamp })
+}
+
+///Parses a JSON object into an InputRecord, numbered as the specified line:
+/// e.g. `{"type": "deposit", "client": 1, "tx": 2, "amount": "3.0"}`, with an optional `"timestamp"`.
+/// Each field can be a string or a number; a missing or null field is absent.
+/// Amounts (and epoch timestamps) are parsed from their text, so a number's exact decimal digits are kept.
+pub fn parse_json(value: &Value, line: u64) -> Result<InputRecord, InputError> {
+
+    parse_json_value(value, line, || value.to_string())
+}
+
+///Parses a JSON object into an InputRecord, with `raw` as the record's text for errors
+fn parse_json_value<F: Fn() -> String>(value: &Value, line: u64, raw: F) -> Result<InputRecord, InputError> {
+
+    let object = value.as_object().ok_or_else(|| InputError::Unreadable { line, raw: raw() })?;
+
+    let text = |name: &str| match object.get(name) {
+        None | Some(Value::Null) => None,
+        Some(Value::String(text)) => Some(text.trim().to_string()),
+        Some(other) => Some(other.to_string()),
+    };
+
+    let fields = [text("type"), text("client"), text("tx"), text("amount"), text("timestamp")];
+
+    parse_fields(fields.each_ref().map(Option::as_deref), line, raw)
+}
+
+///The column order for a line without a header: `type,client,tx,amount,timestamp`
+const DEFAULT_COLUMNS: Columns = Columns { r#type: 0, client: 1, tx: 2, amount: Some(3), timestamp: Some(4) };
+
+///Parses a single CSV line (with no header, so in the default column order: `type,client,tx,amount,timestamp`)
+/// into an InputRecord, numbered as the specified line
+pub fn parse_line(text: &str, line: u64) -> Result<InputRecord, InputError> {
 
     let mut reader = ReaderBuilder::new()
+            .has_headers(false)
             .flexible(true)
-            .trim(Trim::All)
-            .from_reader(input_csv.as_bytes());
+            .from_reader(text.as_bytes());
+
+    let mut string_record = StringRecord::new();
+
+    match reader.read_record(&mut string_record) {
+        Ok(true) => parse_string_record(&string_record, &DEFAULT_COLUMNS, line),
+        Ok(false) => Err(InputError::TruncatedLine { line, raw: text.to_string() }),
+        Err(e) => Err(InputError::Unreadable { line, raw: e.to_string() }),
+    }
+}
 
-    let mut records = Vec::<InputRecord>::new();
+///Lazily parses JSON Lines input (one JSON object per line, see `parse_json`) from a reader into InputRecords:
+/// records are read one at a time, as the returned iterator is advanced.
+/// Lines are numbered from 1 (there's no header line); blank lines are skipped.
+pub fn read_jsonl<R: Read>(input: R) -> JsonlRecords<R> {
 
-    for record in reader.deserialize() {
+    JsonlRecords { reader: BufReader::new(input), line: 0, buffer: Vec::new(), finished: false }
+}
+
+///An iterator over the InputRecords in JSON Lines input (see `read_jsonl`)
+pub struct JsonlRecords<R> {
+    reader: BufReader<R>,
+    //the last line number read
+    line: u64,
+    //reused for each line, to avoid reallocation
+    buffer: Vec<u8>,
+    //set after a read error, which may not be recoverable
+    finished: bool,
+}
+
+impl<R: Read> JsonlRecords<R> {
+
+    ///Reads the next record, or None at the end of the input
+    fn read_next(&mut self) -> Result<Option<InputRecord>, InputError> {
+
+        loop {
+            self.buffer.clear();
+            self.line += 1;
+            let line = self.line;
+
+            if self.reader.read_until(b'\n', &mut self.buffer).map_err(|e| {
+                self.finished = true;
+                InputError::Unreadable { line, raw: e.to_string() }
+            })? == 0 {
+                return Ok(None);
+            }
+
+            let text = String::from_utf8_lossy(&self.buffer);
+            let text = text.trim();
+
+            if text.is_empty() {
+                continue;
+            }
+
+            let value: Value = serde_json::from_str(text)
+                .map_err(|_| InputError::Unreadable { line, raw: text.to_string() })?;
+
+            return parse_json_value(&value, line, || text.to_string()).map(Some);
+        }
+    }
+}
+
+impl<R: Read> Iterator for JsonlRecords<R> {
+    type Item = Result<InputRecord, InputError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
 
-        records.push(record?);
+        if self.finished {
+            return None;
+        }
+        self.read_next().transpose()
+    }
+}
+
+///An input file format
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum InputFormat {
+    ///CSV, with a header line
+    Csv,
+    ///JSON Lines: one JSON object per line
+    Jsonl,
+}
+
+impl InputFormat {
+
+    ///The format implied by a path's extension: `.jsonl` (or `.ndjson`) is JSON Lines, anything else is CSV
+    pub fn from_path(path: &str) -> Self {
+
+        match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+            Some("jsonl") | Some("ndjson") => InputFormat::Jsonl,
+            _ => InputFormat::Csv,
+        }
     }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    ///Parses `csv` or `jsonl`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
 
-    Ok(records)
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" => Ok(InputFormat::Jsonl),
+            _ => Err(format!("invalid input format: {}", s)),
+        }
+    }
 }
 
+///Lazily parses input in the specified format from a reader into InputRecords
+pub fn read_input<'a, R: Read + 'a>(input: R, format: InputFormat) -> Box<dyn Iterator<Item = Result<InputRecord, InputError>> + 'a> {
+
+    match format {
+        InputFormat::Csv => Box::new(read_csv(input)),
+        InputFormat::Jsonl => Box::new(read_jsonl(input)),
+    }
+}
+
+///Parses a CSV string into InputRecords
+pub fn parse_csv(input_csv: String) -> Result<Vec<InputRecord>, InputError> {
+
+    read_csv(input_csv.as_bytes()).collect()
+}
 
 #[cfg(test)]
 mod test {
@@ -55,7 +546,7 @@ deposit,1,2,3.000"
                     .to_string()).unwrap();
 
             let expected = vec! [
-                InputRecord {r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(3.0)},
+                InputRecord {r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(3_0000)), line: 2, timestamp: None},
             ];
 
             assert_eq!(result, expected);
@@ -71,9 +562,9 @@ chargeback,      7,8"
                     .to_string()).unwrap();
 
             let expected = vec! [
-                InputRecord {r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(3.0)},
-                InputRecord {r#type: "withdrawal".to_string(), client: 4, tx: 5, amount: Some(6.0)},
-                InputRecord {r#type: "chargeback".to_string(), client: 7, tx: 8, amount: None},
+                InputRecord {r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(3_0000)), line: 2, timestamp: None},
+                InputRecord {r#type: "withdrawal".to_string(), client: 4, tx: 5, amount: Some(Amount(6_0000)), line: 3, timestamp: None},
+                InputRecord {r#type: "chargeback".to_string(), client: 7, tx: 8, amount: None, line: 4, timestamp: None},
             ];
 
             assert_eq!(result, expected);
@@ -86,10 +577,36 @@ chargeback,      7,8"
 deposit,1"
                     .to_string());
 
-            assert!(matches!(result, Err(_)));
+            assert_eq!(result, Err(InputError::TruncatedLine { line: 2, raw: "deposit,1".to_string() }));
         }
 
 
+        //exact amounts, up to 4 places past the decimal
+        {
+            let result = parse_csv(
+"type, client,  tx, amount
+deposit,1,2,0.1234
+withdrawal,1,3,5"
+                    .to_string()).unwrap();
+
+            let expected = vec! [
+                InputRecord {r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(1234)), line: 2, timestamp: None},
+                InputRecord {r#type: "withdrawal".to_string(), client: 1, tx: 3, amount: Some(Amount(5_0000)), line: 3, timestamp: None},
+            ];
+
+            assert_eq!(result, expected);
+        }
+
+        //failure: amount has more than 4 places past the decimal
+        {
+            let result = parse_csv(
+"type, client,  tx, amount
+deposit,1,2,3.00001"
+                    .to_string());
+
+            assert_eq!(result, Err(InputError::BadAmount { line: 2, raw: "deposit,1,2,3.00001".to_string(), client: 1, tx: 2 }));
+        }
+
         //failure: record element is the wrong type
         {
             let result = parse_csv(
@@ -97,7 +614,227 @@ deposit,1"
 deposit,not_an_integer,2,3.000"
                     .to_string());
 
-            assert!(matches!(result, Err(_)));
+            assert_eq!(result, Err(InputError::BadClientId { line: 2, raw: "deposit,not_an_integer,2,3.000".to_string() }));
         }
+
+        //failure: transaction ID is out of range (raw text is preserved)
+        {
+            let result = parse_csv(
+"type, client,  tx, amount
+deposit,1,2,3.0
+dispute,  1, -2"
+                    .to_string());
+
+            assert_eq!(result, Err(InputError::BadTxId { line: 3, raw: "dispute,  1, -2".to_string() }));
+        }
+
+        //failure: header doesn't name the required columns
+        {
+            let result = parse_csv(
+"type, client, amount
+deposit,1,3.0"
+                    .to_string());
+
+            assert_eq!(result, Err(InputError::BadHeader { line: 1, raw: "type, client, amount".to_string() }));
+        }
+    }
+
+    #[test]
+    fn parse_policy_test() {
+
+        //strict: no invalid lines
+        assert!(ParsePolicy::Strict.allows(0));
+        assert!(!ParsePolicy::Strict.allows(1));
+
+        //lenient: any number of invalid lines
+        assert!(ParsePolicy::Lenient.allows(0));
+        assert!(ParsePolicy::Lenient.allows(1_000_000));
+
+        //threshold: up to N invalid lines
+        assert!(ParsePolicy::Threshold(2).allows(2));
+        assert!(!ParsePolicy::Threshold(2).allows(3));
+        assert!(!ParsePolicy::Threshold(0).allows(1));
+
+        //parsing
+        assert_eq!("strict".parse(), Ok(ParsePolicy::Strict));
+        assert_eq!("lenient".parse(), Ok(ParsePolicy::Lenient));
+        assert_eq!("threshold:5".parse(), Ok(ParsePolicy::Threshold(5)));
+        assert!("threshold:".parse::<ParsePolicy>().is_err());
+        assert!("threshold:-1".parse::<ParsePolicy>().is_err());
+        assert!("relaxed".parse::<ParsePolicy>().is_err());
+    }
+
+    #[test]
+    fn input_error_test() {
+
+        let error = InputError::BadAmount { line: 5, raw: "deposit,1,2,abc".to_string(), client: 1, tx: 2 };
+
+        assert_eq!(error.kind(), InputErrorKind::BadAmount);
+        assert_eq!(error.line(), 5);
+        assert_eq!(error.raw(), "deposit,1,2,abc");
+        assert_eq!(error.ids(), Some((1, 2)));
+        assert_eq!(error.to_string(), "line 5: invalid amount: deposit,1,2,abc");
+    }
+
+    #[test]
+    fn parse_line_test() {
+
+        //success
+        assert_eq!(parse_line("deposit, 1, 2, 3.0", 7),
+            Ok(InputRecord {r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(3_0000)), line: 7, timestamp: None}));
+        assert_eq!(parse_line("dispute,1,2", 8),
+            Ok(InputRecord {r#type: "dispute".to_string(), client: 1, tx: 2, amount: None, line: 8, timestamp: None}));
+        assert_eq!(parse_line("dispute,1,2,", 9),
+            Ok(InputRecord {r#type: "dispute".to_string(), client: 1, tx: 2, amount: None, line: 9, timestamp: None}));
+        assert_eq!(parse_line("dispute,1,2,,1624278600", 10),
+            Ok(InputRecord {r#type: "dispute".to_string(), client: 1, tx: 2, amount: None, line: 10, timestamp: Some(Timestamp(1_624_278_600_000_000_000))}));
+
+        //failure: the same errors as in CSV input
+        assert_eq!(parse_line("", 1), Err(InputError::TruncatedLine { line: 1, raw: "".to_string() }));
+        assert_eq!(parse_line("deposit,1", 2), Err(InputError::TruncatedLine { line: 2, raw: "deposit,1".to_string() }));
+        assert_eq!(parse_line("deposit,x,2,3.0", 3), Err(InputError::BadClientId { line: 3, raw: "deposit,x,2,3.0".to_string() }));
+        assert_eq!(parse_line("deposit,1,2,3.00001", 4), Err(InputError::BadAmount { line: 4, raw: "deposit,1,2,3.00001".to_string(), client: 1, tx: 2 }));
+        assert_eq!(parse_line("deposit,1,2,3.0,today", 5), Err(InputError::BadTimestamp { line: 5, raw: "deposit,1,2,3.0,today".to_string(), client: 1, tx: 2 }));
+    }
+
+    #[test]
+    fn parse_json_test() {
+
+        let parse = |json: &str, line: u64| parse_json(&serde_json::from_str(json).unwrap(), line);
+
+        //success: strings or numbers
+        assert_eq!(parse(r#"{"type": "deposit", "client": 1, "tx": 2, "amount": "3.0"}"#, 1),
+            Ok(InputRecord {r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(3_0000)), line: 1, timestamp: None}));
+        assert_eq!(parse(r#"{"type": " deposit ", "client": "1", "tx": "2", "amount": 0.1234}"#, 2),
+            Ok(InputRecord {r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(1234)), line: 2, timestamp: None}));
+
+        //success: a long numeric amount keeps its exact digits
+        assert_eq!(parse(r#"{"type": "deposit", "client": 1, "tx": 2, "amount": 1234567890123.4567}"#, 2).unwrap().amount,
+            Some(Amount(12345678901234567)));
+
+        //success: no amount
+        assert_eq!(parse(r#"{"type": "dispute", "client": 1, "tx": 2}"#, 3),
+            Ok(InputRecord {r#type: "dispute".to_string(), client: 1, tx: 2, amount: None, line: 3, timestamp: None}));
+        assert_eq!(parse(r#"{"type": "dispute", "client": 1, "tx": 2, "amount": null}"#, 4),
+            Ok(InputRecord {r#type: "dispute".to_string(), client: 1, tx: 2, amount: None, line: 4, timestamp: None}));
+
+        //success: timestamps, as RFC 3339 or epoch seconds
+        assert_eq!(parse(r#"{"type": "dispute", "client": 1, "tx": 2, "timestamp": "2021-06-21T12:30:00Z"}"#, 5).unwrap().timestamp,
+            Some(Timestamp(1_624_278_600_000_000_000)));
+        assert_eq!(parse(r#"{"type": "dispute", "client": 1, "tx": 2, "timestamp": 1624278600.5}"#, 5).unwrap().timestamp,
+            Some(Timestamp(1_624_278_600_500_000_000)));
+
+        //failure: the same errors as in CSV input
+        assert_eq!(parse(r#"{"type": "deposit", "client": 1}"#, 5),
+            Err(InputError::TruncatedLine { line: 5, raw: r#"{"client":1,"type":"deposit"}"#.to_string() }));
+        assert_eq!(parse(r#"{"type": "deposit", "client": -1, "tx": 2}"#, 6),
+            Err(InputError::BadClientId { line: 6, raw: r#"{"client":-1,"tx":2,"type":"deposit"}"#.to_string() }));
+        assert_eq!(parse(r#"{"type": "deposit", "client": 1, "tx": 2.5}"#, 7),
+            Err(InputError::BadTxId { line: 7, raw: r#"{"client":1,"tx":2.5,"type":"deposit"}"#.to_string() }));
+        assert_eq!(parse(r#"{"type": "deposit", "client": 1, "tx": 2, "amount": "3.00001"}"#, 8),
+            Err(InputError::BadAmount { line: 8, raw: r#"{"amount":"3.00001","client":1,"tx":2,"type":"deposit"}"#.to_string(), client: 1, tx: 2 }));
+        assert_eq!(parse(r#"{"type": "deposit", "client": 1, "tx": 2, "amount": 99999999999.99999}"#, 8),
+            Err(InputError::BadAmount { line: 8, raw: r#"{"amount":99999999999.99999,"client":1,"tx":2,"type":"deposit"}"#.to_string(), client: 1, tx: 2 }));
+
+        assert_eq!(parse(r#"{"type": "dispute", "client": 1, "tx": 2, "timestamp": true}"#, 9),
+            Err(InputError::BadTimestamp { line: 9, raw: r#"{"client":1,"timestamp":true,"tx":2,"type":"dispute"}"#.to_string(), client: 1, tx: 2 }));
+
+        //failure: not an object
+        assert_eq!(parse(r#"[1, 2]"#, 9), Err(InputError::Unreadable { line: 9, raw: "[1,2]".to_string() }));
+    }
+
+    #[test]
+    fn read_jsonl_test() {
+
+        //the same records and errors as the equivalent CSV (whose line numbers include the header)
+        let csv = "type, client, tx, amount
+deposit, 1, 2, 3.0
+dispute,1,2
+deposit,x,3,1.0
+deposit,1
+withdrawal,1,5,1.00001
+";
+        let jsonl = r#"{"type": "deposit", "client": 1, "tx": 2, "amount": "3.0"}
+{"type": "dispute", "client": 1, "tx": 2}
+
+{"type": "deposit", "client": "x", "tx": 3, "amount": 1.0}
+{"type": "deposit", "client": 1}
+{"type": "withdrawal", "client": 1, "tx": 5, "amount": 1.00001}
+not json
+"#;
+
+        let from_csv: Vec<_> = read_csv(csv.as_bytes()).collect();
+        let from_jsonl: Vec<_> = read_jsonl(jsonl.as_bytes()).collect();
+
+        assert_eq!(from_jsonl, vec![
+            Ok(InputRecord {r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(3_0000)), line: 1, timestamp: None}),
+            Ok(InputRecord {r#type: "dispute".to_string(), client: 1, tx: 2, amount: None, line: 2, timestamp: None}),
+            Err(InputError::BadClientId { line: 4, raw: r#"{"type": "deposit", "client": "x", "tx": 3, "amount": 1.0}"#.to_string() }),
+            Err(InputError::TruncatedLine { line: 5, raw: r#"{"type": "deposit", "client": 1}"#.to_string() }),
+            Err(InputError::BadAmount { line: 6, raw: r#"{"type": "withdrawal", "client": 1, "tx": 5, "amount": 1.00001}"#.to_string(), client: 1, tx: 5 }),
+            Err(InputError::Unreadable { line: 7, raw: "not json".to_string() }),
+        ]);
+
+        //apart from line numbering and raw text
+        let kind = |result: &Result<InputRecord, InputError>| match result {
+            Ok(record) => Ok(InputRecord { line: 0, ..record.clone() }),
+            Err(e) => Err(e.parts().0),
+        };
+        assert_eq!(from_csv.iter().map(kind).collect::<Vec<_>>(), from_jsonl[..5].iter().map(kind).collect::<Vec<_>>());
+
+        //empty
+        assert_eq!(read_jsonl("".as_bytes()).count(), 0);
+        assert_eq!(read_jsonl("\n\n".as_bytes()).count(), 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn input_format_test() {
+
+        assert_eq!(InputFormat::from_path("in.csv"), InputFormat::Csv);
+        assert_eq!(InputFormat::from_path("in"), InputFormat::Csv);
+        assert_eq!(InputFormat::from_path("dir/in.jsonl"), InputFormat::Jsonl);
+        assert_eq!(InputFormat::from_path("in.ndjson"), InputFormat::Jsonl);
+
+        assert_eq!("csv".parse(), Ok(InputFormat::Csv));
+        assert_eq!("jsonl".parse(), Ok(InputFormat::Jsonl));
+        assert!("json".parse::<InputFormat>().is_err());
+    }
+
+    #[test]
+    fn read_csv_test() {
+
+        //records are read lazily: a bad record doesn't affect those before it
+        {
+            let input = "type, client,  tx, amount
+deposit,1,2,3.0
+deposit,not_an_integer,2,3.000
+withdrawal,1,3,1.0";
+
+            let mut records = read_csv(input.as_bytes());
+
+            assert_eq!(records.next().unwrap().unwrap(),
+                InputRecord {r#type: "deposit".to_string(), client: 1, tx: 2, amount: Some(Amount(3_0000)), line: 2, timestamp: None});
+            assert!(matches!(records.next(), Some(Err(InputError::BadClientId { line: 3, .. }))));
+            assert_eq!(records.next().unwrap().unwrap(),
+                InputRecord {r#type: "withdrawal".to_string(), client: 1, tx: 3, amount: Some(Amount(1_0000)), line: 4, timestamp: None});
+            assert!(records.next().is_none());
+        }
+
+        //an optional timestamp column, in any position: RFC 3339 or epoch seconds
+        {
+            let input = "timestamp, type, client, tx, amount
+2021-06-21T12:30:00Z,deposit,1,2,3.0
+1624278600.25,withdrawal,1,3,1.0
+,dispute,1,2,
+yesterday,dispute,1,2,";
+
+            let mut records = read_csv(input.as_bytes());
+
+            assert_eq!(records.next().unwrap().unwrap().timestamp, Some(Timestamp(1_624_278_600_000_000_000)));
+            assert_eq!(records.next().unwrap().unwrap().timestamp, Some(Timestamp(1_624_278_600_250_000_000)));
+            assert_eq!(records.next().unwrap().unwrap().timestamp, None);
+            assert!(matches!(records.next(), Some(Err(InputError::BadTimestamp { line: 5, .. }))));
+            assert!(records.next().is_none());
+        }
+    }
+}
@@ -0,0 +1,645 @@
+//! An append-only write-ahead journal, for crash recovery:
+//! each admitted transaction record is appended to the journal before it's applied,
+//! so the engine's state can be rebuilt by replaying the journal on top of the latest snapshot
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use crate::engine::{Engine,EngineConfig,Rejection};
+use crate::input::InputRecord;
+
+
+/// The size of an entry's header: payload length (u32), checksum (u32), and sequence number (u64)
+const HEADER_LEN: usize = 16;
+
+/// The longest valid payload: a longer length in a header can only be corruption
+const MAX_PAYLOAD_LEN: usize = 4096;
+
+/// A journal that couldn't be read or written
+#[derive(Debug)]
+pub enum JournalError {
+    Io(std::io::Error),
+    /// An invalid entry that isn't at the end of the journal, so it's not just an interrupted append
+    Corrupt { offset: u64 },
+    /// The journal was written by an engine with a different config
+    ConfigMismatch { journaled: EngineConfig, config: EngineConfig },
+    /// Replaying the entry with this sequence number had a different result than when it was journaled
+    Mismatch { sequence: u64 },
+    /// An earlier write failed, so the journal may end in a partial entry: nothing more can be appended
+    Failed,
+}
+
+impl Display for JournalError {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JournalError::Io(e) => write!(f, "journal I/O error: {}", e),
+            JournalError::Corrupt { offset } => write!(f, "corrupt journal entry at byte {}", offset),
+            JournalError::ConfigMismatch { journaled, config } =>
+                write!(f, "journal was written with {:?}, but replayed with {:?}", journaled, config),
+            JournalError::Mismatch { sequence } =>
+                write!(f, "replaying journal entry {} had a different result than the journaled one", sequence),
+            JournalError::Failed => write!(f, "journal can't be appended to after a failed write"),
+        }
+    }
+}
+
+impl Error for JournalError {}
+
+impl From<std::io::Error> for JournalError {
+
+    fn from(e: std::io::Error) -> Self {
+        JournalError::Io(e)
+    }
+}
+
+/// An entry's payload
+#[derive(PartialEq,Debug)]
+enum Payload {
+    /// The config of the engine the journal's records are applied to: always the first entry
+    Config(EngineConfig),
+    /// An admitted transaction record, appended before it's applied
+    Record(InputRecord),
+    /// The result of applying the record with the same sequence number: "ok", or its rejection reason
+    Outcome(String),
+}
+
+/// The result of reading an entry
+#[derive(PartialEq,Debug)]
+enum ReadEntry {
+    /// The end of the journal
+    End,
+    /// A valid entry: its sequence number, payload, and length
+    Entry(u64, Payload, u64),
+    /// An incomplete entry, or one that fails its checksum
+    Invalid,
+    /// An entry whose header has an impossible payload length
+    BadLength,
+}
+
+/// An open journal file.
+///
+/// Each entry is a header (little-endian payload length, CRC-32 checksum of the sequence number and payload,
+/// and sequence number), then the payload, as text: the engine's config (as JSON), a record (its line number,
+/// then the record as a CSV line; see `InputRecord`'s `Display`), or a record's outcome.
+/// Sequence numbers increase across the journal's lifetime (even when it's truncated),
+/// and the engine tracks the last one it applied, so no entry is ever applied twice.
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+    //the config entry written at the start of the journal
+    config: EngineConfig,
+    //the last sequence number used
+    sequence: u64,
+    //the number of records appended between syncs (1: each record is synced before it's applied)
+    group_size: usize,
+    //records appended since the last sync
+    unsynced: usize,
+    //set by a failed write or sync
+    failed: bool,
+}
+
+impl Journal {
+
+    /// Opens the journal at `path` (creating it if needed) and replays each entry `engine` hasn't applied yet,
+    /// checking that the journal was written with `engine`'s config and that each replayed record has its journaled outcome.
+    /// An incomplete or invalid last entry (i.e. an interrupted append) is discarded;
+    /// any other invalid entry is reported as corruption, and nothing is discarded.
+    pub fn open<P: AsRef<Path>>(path: P, engine: &mut Engine) -> Result<Self, JournalError> {
+
+        let file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+
+        let mut reader = BufReader::new(&file);
+        let mut sequence = engine.journal_sequence;
+        let mut offset = 0;
+
+        //the last replayed record's sequence number and outcome, until its journaled outcome is checked
+        let mut replayed: Option<(u64, String)> = None;
+
+        loop {
+            let (entry_sequence, payload, len) = match read_entry(&mut reader)? {
+                ReadEntry::End => break,
+                ReadEntry::Entry(entry_sequence, payload, len) => (entry_sequence, payload, len),
+
+                //only the last entry can be incomplete
+                ReadEntry::Invalid if reader.fill_buf()?.is_empty() => {
+                    file.set_len(offset)?;
+                    file.sync_data()?;
+                    break;
+                },
+                ReadEntry::Invalid | ReadEntry::BadLength => return Err(JournalError::Corrupt { offset }),
+            };
+
+            match payload {
+                Payload::Config(journaled) => {
+                    if journaled != engine.config() {
+                        return Err(JournalError::ConfigMismatch { journaled, config: engine.config() });
+                    }
+                },
+                //every journal starts with its config
+                _ if offset == 0 => return Err(JournalError::Corrupt { offset }),
+
+                //entries already in the engine's state (e.g. from a snapshot) are skipped
+                Payload::Record(record) => {
+                    if entry_sequence > engine.journal_sequence {
+                        let result = engine.apply(&record);
+                        engine.journal_sequence = entry_sequence;
+                        replayed = Some((entry_sequence, outcome(&result)));
+                    }
+                },
+                Payload::Outcome(journaled) => {
+                    if let Some((replayed_sequence, outcome)) = replayed.take() {
+                        if replayed_sequence == entry_sequence && outcome != journaled {
+                            return Err(JournalError::Mismatch { sequence: entry_sequence });
+                        }
+                    }
+                },
+            }
+
+            sequence = sequence.max(entry_sequence);
+            offset += len;
+        }
+
+        let mut journal = Self { file, config: engine.config(), sequence, group_size: 1, unsynced: 0, failed: false };
+
+        if offset == 0 {
+            journal.start()?;
+        }
+
+        Ok(journal)
+    }
+
+    /// Sets the number of records appended between syncs, instead of syncing each record before it's applied:
+    /// for when results aren't reported until `sync` is called.
+    /// A crash can then lose the records appended since the last sync.
+    pub fn set_group_size(&mut self, group_size: usize) {
+        self.group_size = group_size.max(1);
+    }
+
+    /// Appends a record, and records its sequence number as the last one applied by `engine`.
+    /// Unless records are synced in groups, waits for it to reach the storage device.
+    pub(crate) fn append(&mut self, record: &InputRecord, engine: &mut Engine) -> Result<(), JournalError> {
+
+        let sequence = self.sequence + 1;
+        self.write_entry(sequence, &record_payload(record))?;
+
+        self.unsynced += 1;
+        if self.unsynced >= self.group_size {
+            self.sync()?;
+        }
+
+        self.sequence = sequence;
+        engine.journal_sequence = sequence;
+        Ok(())
+    }
+
+    /// Appends the outcome of the last appended record, for replay to check.
+    /// It's synced along with the next record.
+    pub(crate) fn append_outcome(&mut self, result: &Result<(), Rejection>) -> Result<(), JournalError> {
+        self.write_entry(self.sequence, &encode_payload(&Payload::Outcome(outcome(result))))
+    }
+
+    /// Waits for the journal's entries to reach the storage device
+    pub fn sync(&mut self) -> Result<(), JournalError> {
+
+        if self.failed {
+            return Err(JournalError::Failed);
+        }
+
+        //after a failed sync, it's unknown which entries reached the storage device
+        let result = self.file.sync_data();
+        self.failed = result.is_err();
+        result?;
+
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    /// Fails the journal, as a failed write would
+    #[cfg(test)]
+    pub(crate) fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    /// Discards every entry: for use once they're all included in a saved snapshot
+    pub fn truncate(&mut self) -> Result<(), JournalError> {
+
+        if self.failed {
+            return Err(JournalError::Failed);
+        }
+
+        self.file.set_len(0)?;
+        self.start()
+    }
+
+    /// Starts an empty journal with its config entry
+    fn start(&mut self) -> Result<(), JournalError> {
+
+        self.write_entry(self.sequence, &encode_payload(&Payload::Config(self.config)))?;
+        self.sync()
+    }
+
+    fn write_entry(&mut self, sequence: u64, payload: &str) -> Result<(), JournalError> {
+
+        if self.failed {
+            return Err(JournalError::Failed);
+        }
+
+        //the entry is written with a single write, so an interruption can only truncate it
+        let result = self.file.write_all(&encode_entry(sequence, payload));
+        self.failed = result.is_err();
+        result?;
+
+        Ok(())
+    }
+}
+
+impl Engine {
+
+    /// Like `apply`, but appends the record to the journal first, if it's admitted (and its outcome, after it's applied).
+    /// Returns an error if the record can't be journaled (then it isn't applied).
+    pub fn apply_journaled(&mut self, record: &InputRecord, journal: &mut Journal) -> Result<Result<(), Rejection>, JournalError> {
+
+        let (client_id, transaction, now) = match self.admit(record) {
+            Ok(admitted) => admitted,
+            Err(rejection) => return Ok(Err(rejection)),
+        };
+
+        journal.append(record, self)?;
+
+        let result = self.apply_admitted(client_id, &transaction, now)
+            .map_err(|reason| Rejection::new(record, reason));
+
+        //the record is applied either way: a failed outcome write fails the journal, so the next append or sync reports it
+        let _ = journal.append_outcome(&result);
+        Ok(result)
+    }
+}
+
+/// A record's outcome, as journaled
+fn outcome(result: &Result<(), Rejection>) -> String {
+
+    match result {
+        Ok(()) => "ok".to_string(),
+        Err(rejection) => rejection.reason.to_string(),
+    }
+}
+
+/// The entry's checksum: covers its sequence number and payload
+fn checksum(sequence: u64, payload: &[u8]) -> u32 {
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&sequence.to_le_bytes());
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+fn encode_entry(sequence: u64, payload: &str) -> Vec<u8> {
+
+    let payload = payload.as_bytes();
+
+    let mut entry = Vec::with_capacity(HEADER_LEN + payload.len());
+    entry.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    entry.extend_from_slice(&checksum(sequence, payload).to_le_bytes());
+    entry.extend_from_slice(&sequence.to_le_bytes());
+    entry.extend_from_slice(payload);
+    entry
+}
+
+/// Reads the next entry.
+/// After an invalid entry, `reader` is at the end of the entry's bytes (as far as they go).
+fn read_entry<R: Read>(reader: &mut R) -> std::io::Result<ReadEntry> {
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    reader.by_ref().take(HEADER_LEN as u64).read_to_end(&mut header)?;
+
+    match header.len() {
+        0 => return Ok(ReadEntry::End),
+        HEADER_LEN => {},
+        _ => return Ok(ReadEntry::Invalid),
+    }
+
+    let payload_len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    if payload_len > MAX_PAYLOAD_LEN {
+        return Ok(ReadEntry::BadLength);
+    }
+
+    let expected_checksum = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let mut sequence = [0; 8];
+    sequence.copy_from_slice(&header[8..]);
+    let sequence = u64::from_le_bytes(sequence);
+
+    let mut payload = Vec::with_capacity(payload_len);
+    reader.by_ref().take(payload_len as u64).read_to_end(&mut payload)?;
+
+    if payload.len() < payload_len || checksum(sequence, &payload) != expected_checksum {
+        return Ok(ReadEntry::Invalid);
+    }
+
+    Ok(match std::str::from_utf8(&payload).ok().and_then(decode_payload) {
+        Some(payload) => ReadEntry::Entry(sequence, payload, (HEADER_LEN + payload_len) as u64),
+        None => ReadEntry::Invalid,
+    })
+}
+
+fn encode_payload(payload: &Payload) -> String {
+
+    match payload {
+        Payload::Config(config) => format!("config,{}", serde_json::to_string(config).expect("config is serializable")),
+        Payload::Record(record) => record_payload(record),
+        Payload::Outcome(outcome) => format!("outcome,{}", outcome),
+    }
+}
+
+/// A record's payload: `record,`, its line number, then the record as a CSV line
+fn record_payload(record: &InputRecord) -> String {
+    format!("record,{},{}", record.line, record)
+}
+
+fn decode_payload(payload: &str) -> Option<Payload> {
+
+    let (kind, payload) = payload.split_once(',')?;
+
+    match kind {
+        "config" => Some(Payload::Config(serde_json::from_str(payload).ok()?)),
+        "record" => Some(Payload::Record(decode_record(payload)?)),
+        "outcome" => Some(Payload::Outcome(payload.to_string())),
+        _ => None,
+    }
+}
+
+/// Parses a record payload: `line,type,client,tx,amount`, then `,timestamp` if the record has one
+fn decode_record(payload: &str) -> Option<InputRecord> {
+
+    let mut fields = payload.split(',');
+
+    let line = fields.next()?.parse().ok()?;
+    let r#type = fields.next()?.to_string();
+    let client = fields.next()?.parse().ok()?;
+    let tx = fields.next()?.parse().ok()?;
+    let amount = match fields.next()? {
+        "" => None,
+        amount => Some(amount.parse().ok()?),
+    };
+
+    let timestamp = match fields.next() {
+        None => None,
+        Some(timestamp) => Some(timestamp.parse().ok()?),
+    };
+
+    if fields.next().is_some() {
+        return None;
+    }
+
+    Some(InputRecord { r#type, client, tx, amount, line, timestamp })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use crate::amount::Amount;
+    use crate::engine::Window;
+
+    fn records() -> Vec<InputRecord> {
+        vec![
+            InputRecord{r#type: "deposit".to_string(), client: 1, tx: 1, amount: Some(Amount(10_0000)), line: 2, timestamp: None},
+            InputRecord{r#type: "deposit".to_string(), client: 2, tx: 2, amount: Some(Amount(5_0000)), line: 3, timestamp: None},
+            InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 3, amount: Some(Amount(20_0000)), line: 4, timestamp: None},
+            InputRecord{r#type: "dispute".to_string(), client: 1, tx: 1, amount: None, line: 5, timestamp: None},
+            InputRecord{r#type: "bogus".to_string(), client: 1, tx: 4, amount: None, line: 6, timestamp: None},
+            InputRecord{r#type: "deposit".to_string(), client: 2, tx: 1, amount: Some(Amount(1_0000)), line: 7, timestamp: None},
+            InputRecord{r#type: "resolve".to_string(), client: 1, tx: 1, amount: None, line: 8, timestamp: None},
+        ]
+    }
+
+    /// A new journal file path, with no file present
+    fn journal_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("rust2021_6_21_journal_{}.bin", name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// A journal's entries: the default config, then each record (without outcomes)
+    fn journal_bytes(records: &[InputRecord]) -> Vec<u8> {
+
+        let mut entries = encode_entry(0, &encode_payload(&Payload::Config(EngineConfig::default())));
+        for (sequence, record) in (1..).zip(records) {
+            entries.extend(encode_entry(sequence, &record_payload(record)));
+        }
+        entries
+    }
+
+    #[test]
+    fn entry_test() {
+
+        let timestamped = InputRecord{r#type: "deposit".to_string(), client: 3, tx: 9, amount: Some(Amount(1_0000)), line: 9,
+            timestamp: Some("2021-06-21T12:30:00.25Z".parse().unwrap())};
+
+        let config = EngineConfig {
+            allow_redispute: false,
+            dispute_window: Some(Window::Time(Duration::from_secs(3600))),
+            chargeback_deadline: Some(Window::Transactions(10)),
+        };
+
+        let payloads = records().into_iter().chain([timestamped]).map(Payload::Record)
+            .chain([Payload::Config(config), Payload::Outcome("ok".to_string())]);
+
+        for payload in payloads {
+            let entry = encode_entry(7, &encode_payload(&payload));
+            let len = entry.len() as u64;
+
+            //success
+            assert_eq!(read_entry(&mut entry.as_slice()).unwrap(), ReadEntry::Entry(7, payload, len));
+            assert_eq!(read_entry(&mut &entry[..0]).unwrap(), ReadEntry::End);
+
+            //failure: incomplete
+            assert_eq!(read_entry(&mut &entry[..entry.len() - 1]).unwrap(), ReadEntry::Invalid);
+            assert_eq!(read_entry(&mut &entry[..HEADER_LEN - 1]).unwrap(), ReadEntry::Invalid);
+
+            //failure: checksum mismatch
+            let mut corrupted = entry.clone();
+            *corrupted.last_mut().unwrap() ^= 1;
+            assert_eq!(read_entry(&mut corrupted.as_slice()).unwrap(), ReadEntry::Invalid);
+
+            //failure: impossible length
+            let mut corrupted = entry.clone();
+            corrupted[1] = 0xff;
+            assert_eq!(read_entry(&mut corrupted.as_slice()).unwrap(), ReadEntry::BadLength);
+        }
+
+        //failure: unknown payload kind
+        let entry = encode_entry(7, "bogus,1");
+        assert_eq!(read_entry(&mut entry.as_slice()).unwrap(), ReadEntry::Invalid);
+    }
+
+    #[test]
+    fn replay_test() {
+
+        let records = records();
+
+        let mut expected_engine = Engine::new();
+        let expected: Vec<_> = records.iter().map(|record| expected_engine.apply(record)).collect();
+
+        //success: applying while journaling is the same as applying, and replaying restores the state
+        for group_size in [1, 4] {
+            let path = journal_path("replay");
+
+            let mut engine = Engine::new();
+            let mut journal = Journal::open(&path, &mut engine).unwrap();
+            journal.set_group_size(group_size);
+            let results: Vec<_> = records.iter().map(|record| engine.apply_journaled(record, &mut journal).unwrap()).collect();
+            journal.sync().unwrap();
+
+            assert_eq!(results, expected);
+            assert_eq!(engine.snapshot(), expected_engine.snapshot());
+            drop(journal);
+
+            //unadmitted records (the malformed and duplicate ones) aren't journaled
+            assert_eq!(engine.journal_sequence, 5);
+
+            let mut replayed = Engine::new();
+            Journal::open(&path, &mut replayed).unwrap();
+            assert_eq!(replayed.snapshot(), expected_engine.snapshot());
+        }
+
+        //success: replay on top of a snapshot skips the entries it already includes
+        {
+            let path = journal_path("snapshot");
+
+            let mut engine = Engine::new();
+            let mut journal = Journal::open(&path, &mut engine).unwrap();
+            for record in &records[..3] {
+                engine.apply_journaled(record, &mut journal).unwrap().ok();
+            }
+
+            let mut snapshot = Vec::new();
+            engine.save_snapshot(&mut snapshot).unwrap();
+
+            //more entries, after the snapshot (e.g. before a crash)
+            for record in &records[3..] {
+                engine.apply_journaled(record, &mut journal).unwrap().ok();
+            }
+            drop(journal);
+
+            let mut restored = Engine::load_snapshot(snapshot.as_slice(), EngineConfig::default()).unwrap();
+            let mut journal = Journal::open(&path, &mut restored).unwrap();
+            assert_eq!(restored.snapshot(), expected_engine.snapshot());
+
+            //after truncation, the journal starts with its config again, and sequence numbers continue
+            journal.truncate().unwrap();
+            let record = InputRecord{r#type: "deposit".to_string(), client: 3, tx: 9, amount: Some(Amount(1_0000)), line: 9, timestamp: None};
+            restored.apply_journaled(&record, &mut journal).unwrap().unwrap();
+            assert_eq!(restored.journal_sequence, 6);
+            drop(journal);
+
+            let mut replayed = Engine::new();
+            replayed.journal_sequence = 5;
+            Journal::open(&path, &mut replayed).unwrap();
+            assert_eq!(replayed.journal_sequence, 6);
+        }
+
+        //success: an interrupted append at the end is discarded
+        {
+            let path = journal_path("truncated");
+
+            let mut engine = Engine::new();
+            let mut journal = Journal::open(&path, &mut engine).unwrap();
+            for record in &records[..2] {
+                engine.apply_journaled(record, &mut journal).unwrap().ok();
+            }
+            drop(journal);
+
+            let complete_len = std::fs::metadata(&path).unwrap().len();
+            let partial = encode_entry(3, &record_payload(&records[2]));
+            for len in [1, HEADER_LEN, partial.len() - 1] {
+
+                std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(&partial[..len]).unwrap();
+
+                let mut replayed = Engine::new();
+                Journal::open(&path, &mut replayed).unwrap();
+                assert_eq!(replayed.snapshot(), engine.snapshot());
+                assert_eq!(std::fs::metadata(&path).unwrap().len(), complete_len);
+            }
+        }
+
+        //failure: an invalid entry before the end
+        {
+            let path = journal_path("corrupt");
+
+            let mut entries = journal_bytes(&records[..2]);
+            let first_offset = journal_bytes(&[]).len();
+            let second_offset = journal_bytes(&records[..1]).len();
+            entries[second_offset - 1] ^= 1;
+            std::fs::write(&path, &entries).unwrap();
+
+            let result = Journal::open(&path, &mut Engine::new());
+            assert!(matches!(result, Err(JournalError::Corrupt { offset }) if offset == first_offset as u64));
+        }
+
+        //failure: a corrupted length that points past the end of the journal, before valid entries
+        {
+            let path = journal_path("corrupt_length");
+
+            let mut entries = journal_bytes(&records[..3]);
+            let second_offset = journal_bytes(&records[..1]).len();
+            entries[second_offset + 1] = 0xff;
+            std::fs::write(&path, &entries).unwrap();
+
+            let result = Journal::open(&path, &mut Engine::new());
+            assert!(matches!(result, Err(JournalError::Corrupt { offset }) if offset == second_offset as u64));
+
+            //the journal is left as it was
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), entries.len() as u64);
+        }
+
+        //failure: no config entry
+        {
+            let path = journal_path("no_config");
+
+            std::fs::write(&path, encode_entry(1, &record_payload(&records[0]))).unwrap();
+
+            let result = Journal::open(&path, &mut Engine::new());
+            assert!(matches!(result, Err(JournalError::Corrupt { offset: 0 })));
+        }
+
+        //failure: written with a different config
+        {
+            let path = journal_path("config");
+
+            std::fs::write(&path, journal_bytes(&records[..1])).unwrap();
+
+            let result = Journal::open(&path, &mut Engine::with_config(EngineConfig { allow_redispute: false, ..EngineConfig::default() }));
+            assert!(matches!(result, Err(JournalError::ConfigMismatch { .. })));
+        }
+
+        //failure: nothing more is appended after a failed write
+        {
+            let path = journal_path("failed");
+
+            let mut engine = Engine::new();
+            let mut journal = Journal::open(&path, &mut engine).unwrap();
+            journal.fail();
+
+            assert!(matches!(engine.apply_journaled(&records[0], &mut journal), Err(JournalError::Failed)));
+            assert!(matches!(journal.sync(), Err(JournalError::Failed)));
+            assert_eq!(engine.snapshot(), vec![]);
+        }
+
+        //failure: a replayed record with a different outcome
+        {
+            let path = journal_path("outcome");
+
+            let mut entries = journal_bytes(&records[..1]);
+            entries.extend(encode_entry(1, &encode_payload(&Payload::Outcome("InsufficientFunds".to_string()))));
+            std::fs::write(&path, &entries).unwrap();
+
+            let result = Journal::open(&path, &mut Engine::new());
+            assert!(matches!(result, Err(JournalError::Mismatch { sequence: 1 })));
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! An audit ledger: a line for each applied transaction, with its effect on its client account,
+//! so every account state can be explained by the transactions that led to it
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::account_state::AccountState;
+use crate::amount::Amount;
+use crate::engine::{ClientId,Engine,Rejection};
+use crate::input::InputRecord;
+use crate::journal::{Journal,JournalError};
+use crate::output::csv_writer_with_header;
+
+
+/// The ledger CSV columns, in order
+const COLUMNS: [&str; 9] = ["client", "tx", "type", "available_delta", "held_delta", "available", "held", "total", "locked"];
+
+/// An applied transaction's ledger entry, `Serialize`-able as a ledger CSV line:
+/// its changes to its client account's funds, and the resulting account state
+#[derive(Clone,PartialEq,Debug,Serialize)]
+pub struct LedgerEntry {
+    pub client: u16,
+    pub tx: u32,
+    ///the transaction type, as in the input (e.g. "deposit")
+    pub r#type: String,
+    ///the change to available funds
+    pub available_delta: Amount,
+    ///the change to held funds
+    pub held_delta: Amount,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+}
+
+impl LedgerEntry {
+
+    /// The entry for an applied record, from its client account's state before (None: not open yet) and after
+    pub fn new(record: &InputRecord, before: Option<&AccountState>, after: &AccountState) -> Self {
+
+        let (available_before, held_before) = before.map_or((Amount(0), Amount(0)), |before| (before.available, before.held));
+
+        Self {
+            client: record.client,
+            tx: record.tx,
+            r#type: record.r#type.clone(),
+            available_delta: after.available - available_before,
+            held_delta: after.held - held_before,
+            available: after.available,
+            held: after.held,
+            total: after.total(),
+            locked: after.locked,
+        }
+    }
+}
+
+/// A ledger CSV writer
+pub struct Ledger<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> Ledger<W> {
+
+    /// Starts a ledger: writes its header line
+    pub fn new(writer: W) -> csv::Result<Self> {
+        Ok(Self { writer: csv_writer_with_header(writer, &COLUMNS)? })
+    }
+
+    pub fn write(&mut self, entry: &LedgerEntry) -> csv::Result<()> {
+        self.writer.serialize(entry)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Engine {
+
+    /// Like `apply` (or `apply_journaled`, with a journal), but returns the applied transaction's ledger entry.
+    /// Returns an error if the journal can't be written (then the record isn't applied).
+    pub fn apply_ledgered(&mut self, record: &InputRecord, journal: Option<&mut Journal>)
+        -> Result<Result<LedgerEntry, Rejection>, JournalError> {
+
+        let client_id = ClientId(record.client);
+
+        let before = self.account_state(client_id);
+
+        let result = match journal {
+            Some(journal) => self.apply_journaled(record, journal)?,
+            None => self.apply(record),
+        };
+
+        Ok(result.map(|()| {
+            let after = self.account_state(client_id).expect("an applied transaction's account is open");
+            LedgerEntry::new(record, before.as_ref(), &after)
+        }))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::RejectionReason;
+
+    fn record(r#type: &str, client: u16, tx: u32, amount: Option<Amount>, line: u64) -> InputRecord {
+        InputRecord{r#type: r#type.to_string(), client, tx, amount, line, timestamp: None}
+    }
+
+    #[test]
+    fn apply_ledgered_test() {
+
+        let mut engine = Engine::new();
+        let mut apply = |r#type, tx, amount, line| engine.apply_ledgered(&record(r#type, 1, tx, amount, line), None).unwrap();
+
+        //success: each transaction's changes, and the resulting account state
+        assert_eq!(apply("deposit", 1, Some(Amount(10_0000)), 2).unwrap(), LedgerEntry {
+            client: 1, tx: 1, r#type: "deposit".to_string(),
+            available_delta: Amount(10_0000), held_delta: Amount(0),
+            available: Amount(10_0000), held: Amount(0), total: Amount(10_0000), locked: false,
+        });
+        assert_eq!(apply("withdrawal", 2, Some(Amount(2_5000)), 3).unwrap(), LedgerEntry {
+            client: 1, tx: 2, r#type: "withdrawal".to_string(),
+            available_delta: Amount(-2_5000), held_delta: Amount(0),
+            available: Amount(7_5000), held: Amount(0), total: Amount(7_5000), locked: false,
+        });
+        assert_eq!(apply("dispute", 1, None, 4).unwrap(), LedgerEntry {
+            client: 1, tx: 1, r#type: "dispute".to_string(),
+            available_delta: Amount(-10_0000), held_delta: Amount(10_0000),
+            available: Amount(-2_5000), held: Amount(10_0000), total: Amount(7_5000), locked: false,
+        });
+        assert_eq!(apply("chargeback", 1, None, 5).unwrap(), LedgerEntry {
+            client: 1, tx: 1, r#type: "chargeback".to_string(),
+            available_delta: Amount(0), held_delta: Amount(-10_0000),
+            available: Amount(-2_5000), held: Amount(0), total: Amount(-2_5000), locked: true,
+        });
+
+        //failure: a rejected transaction has no entry
+        assert_eq!(apply("deposit", 3, Some(Amount(1_0000)), 6).unwrap_err().reason, RejectionReason::AccountLocked);
+
+        //success: with a journal, the transaction is journaled too
+        let path = std::env::temp_dir().join("rust2021_6_21_ledger_journal.bin");
+        let _ = std::fs::remove_file(&path);
+
+        let mut engine = Engine::new();
+        let mut journal = Journal::open(&path, &mut engine).unwrap();
+        let entry = engine.apply_ledgered(&record("deposit", 2, 1, Some(Amount(1_0000)), 2), Some(&mut journal)).unwrap().unwrap();
+        assert_eq!(entry.available, Amount(1_0000));
+        assert_eq!(engine.journal_sequence, 1);
+    }
+
+    #[test]
+    fn ledger_test() {
+
+        let mut buffer = Vec::new();
+        {
+            let mut ledger = Ledger::new(&mut buffer).unwrap();
+            let mut engine = Engine::new();
+
+            for record in [
+                record("deposit", 2, 1, Some(Amount(1_5000)), 2),
+                record("withdrawal", 2, 2, Some(Amount(2500)), 3),
+            ] {
+                ledger.write(&engine.apply_ledgered(&record, None).unwrap().unwrap()).unwrap();
+            }
+            ledger.flush().unwrap();
+        }
+
+        assert_eq!(String::from_utf8(buffer).unwrap(),
+"client,tx,type,available_delta,held_delta,available,held,total,locked
+2,1,deposit,1.5000,0.0000,1.5000,0.0000,1.5000,false
+2,2,withdrawal,-0.2500,0.0000,1.2500,0.0000,1.2500,false
+");
+    }
+}
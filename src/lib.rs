@@ -1,3 +1,13 @@
+pub mod amount;
+pub mod timestamp;
 pub mod input;
 pub mod engine;
 pub mod account_state;
+pub mod parallel;
+pub mod snapshot;
+pub mod journal;
+pub mod ledger;
+pub mod output;
+pub mod server;
+#[cfg(feature = "http")]
+pub mod http;
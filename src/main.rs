@@ -1,29 +1,363 @@
 use std::error::Error;
-use std::fs::read_to_string;
+use std::fs::File;
+use std::io::{BufWriter,Write};
 
-use rust2021_6_21::input::parse_csv;
-use rust2021_6_21::engine::run;
+use rust2021_6_21::input::{read_input,InputError,InputFormat,InputRecord,ParsePolicy};
+use rust2021_6_21::engine::{AsOf,AsOfLimit,Engine,OpenDispute,Rejection,RejectionReason};
+use rust2021_6_21::journal::Journal;
+use rust2021_6_21::ledger::Ledger;
+use rust2021_6_21::output::{csv_writer_with_header,write_output};
+use rust2021_6_21::parallel::Pipeline;
+use rust2021_6_21::server::Server;
 
+mod cli;
+use cli::{expand_globs, Options, USAGE};
+
+
+/// The number of transactions each worker thread can have queued, when processing in parallel
+const QUEUE_CAPACITY: usize = 1024;
+
+/// The number of journal entries synced to storage together, when processing input files
+const JOURNAL_GROUP_SIZE: usize = 1024;
+
+/// A reported input line
+enum Report {
+    //a line that couldn't be parsed
+    InvalidLine(InputError),
+    Rejected(Rejection),
+}
+
+impl Report {
+
+    fn line(&self) -> u64 {
+        match self {
+            Report::InvalidLine(e) => e.line(),
+            Report::Rejected(rejection) => rejection.line,
+        }
+    }
+}
+
+/// Checks invalid lines against the parse policy, and collects reports of them and rejected transactions.
+/// Also reports applied admin actions.
+struct Reporter {
+    parse_policy: ParsePolicy,
+    //the number of unparseable or malformed lines so far, in every input file
+    invalid_lines: usize,
+    //the input file paths so far: the last one is being processed
+    files: Vec<String>,
+    //each report, with its input file's index
+    reports: Vec<(usize, Report)>,
+    //applied admin actions are only reported if requested
+    admin_actions_writer: Option<csv::Writer<File>>,
+}
+
+impl Reporter {
+
+    fn new(parse_policy: ParsePolicy, admin_actions_writer: Option<csv::Writer<File>>) -> Self {
+        Self { parse_policy, invalid_lines: 0, files: Vec::new(), reports: Vec::new(), admin_actions_writer }
+    }
+
+    /// Starts reporting on the next input file
+    fn start_file(&mut self, path: &str) {
+        self.files.push(path.to_string());
+    }
+
+    /// The input file being processed
+    fn file(&self) -> &str {
+        self.files.last().map_or("", String::as_str)
+    }
+
+    /// The index of the input file being processed
+    fn file_index(&self) -> usize {
+        self.files.len().saturating_sub(1)
+    }
+
+    fn report(&mut self, report: Report) {
+        self.reports.push((self.file_index(), report));
+    }
+
+    /// Skips an unparseable line, unless the parse policy forbids it
+    fn invalid_line(&mut self, e: InputError) -> Result<(), Box<dyn Error>> {
+
+        self.invalid_lines += 1;
+        if !self.parse_policy.allows(self.invalid_lines) {
+            return Err(format!("{}: {}", self.file(), e).into());
+        }
+
+        //skip this line
+        self.report(Report::InvalidLine(e));
+        Ok(())
+    }
+
+    /// Reports an applied transaction record, if it's an admin action
+    fn applied(&mut self, record: &InputRecord) -> Result<(), Box<dyn Error>> {
+
+        let file = self.file().to_string();
+
+        if let (true, Some(writer)) = (record.is_admin_action(), self.admin_actions_writer.as_mut()) {
+            writer.write_record(&[file, record.line.to_string(), record.client.to_string(), record.tx.to_string(),
+                record.r#type.clone()])?;
+        }
+        Ok(())
+    }
+
+    /// Reports a transaction record's result, if it was rejected
+    fn result(&mut self, result: Result<(), Rejection>) -> Result<(), Box<dyn Error>> {
+
+        if let Err(rejection) = result {
+
+            if let RejectionReason::InvalidRecord(_) = rejection.reason {
+                self.invalid_lines += 1;
+                if !self.parse_policy.allows(self.invalid_lines) {
+                    return Err(format!("{}: {}", self.file(), rejection).into());
+                }
+            }
+
+            self.report(Report::Rejected(rejection));
+        }
+        Ok(())
+    }
+
+    /// Reports a transaction rejected by a pipeline worker, from the input file with index `file_index`
+    fn worker_rejection(&mut self, file_index: usize, rejection: Rejection) {
+        self.reports.push((file_index, Report::Rejected(rejection)));
+    }
+
+    /// Flushes the admin actions report, and prints the number of skipped lines
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+
+        if let Some(ref mut writer) = self.admin_actions_writer {
+            writer.flush()?;
+        }
+
+        //skipped lines are reported individually only in the rejections CSV
+        if self.invalid_lines > 0 {
+            eprintln!("skipped {} invalid lines", self.invalid_lines);
+        }
+        Ok(())
+    }
+
+    /// Writes the reports to a rejections CSV file, in input order
+    fn write(mut self, path: &str) -> Result<(), Box<dyn Error>> {
+
+        //with parallel processing, some rejections are only known at the end
+        self.reports.sort_by_key(|(file, report)| (*file, report.line()));
+
+        let mut writer = csv_writer_with_header(File::create(path)?, &["file", "line", "client", "tx", "reason"])?;
+
+        for (file, report) in self.reports {
+            let file = &self.files[file];
+            match report {
+                //its client and transaction ID are reported if they were parsed
+                Report::InvalidLine(e) => {
+                    let (client, tx) = e.ids().map_or((String::new(), String::new()),
+                        |(client, tx)| (client.to_string(), tx.to_string()));
+
+                    writer.write_record(&[file.clone(), e.line().to_string(), client, tx,
+                        RejectionReason::InvalidRecord(e.kind()).to_string()])?
+                },
+                Report::Rejected(rejection) => writer.serialize((file, rejection))?,
+            }
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Applies a record: journals it first (if there's a journal), and writes its ledger entry (if there's a ledger)
+fn apply<W: Write>(engine: &mut Engine, record: &InputRecord, journal: Option<&mut Journal>, ledger: Option<&mut Ledger<W>>)
+    -> Result<Result<(), Rejection>, Box<dyn Error>> {
+
+    let result = match (ledger, journal) {
+        (Some(ledger), journal) => {
+            let result = engine.apply_ledgered(record, journal)?;
+            if let Ok(ref entry) = result {
+                ledger.write(entry)?;
+            }
+            result.map(|_| ())
+        },
+        (None, Some(journal)) => engine.apply_journaled(record, journal)?,
+        (None, None) => engine.apply(record),
+    };
+
+    Ok(result)
+}
+
+/// Serves the HTTP/JSON API (if this build has the `http` feature)
+#[cfg(feature = "http")]
+fn serve_http(address: &str, engine: Engine, journal: Option<Journal>) -> Result<(), Box<dyn Error>> {
+
+    let server = rust2021_6_21::http::HttpServer::bind(address, engine, journal).map_err(|e| e.to_string())?;
+    if let Some(address) = server.local_addr() {
+        eprintln!("listening on http://{}", address);
+    }
+    server.run();
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+fn serve_http(_address: &str, _engine: Engine, _journal: Option<Journal>) -> Result<(), Box<dyn Error>> {
+    Err("HTTP serve mode requires the http feature (build with --features http)".into())
+}
+
+/// Writes the open disputes to a CSV file
+fn write_open_disputes(path: &str, open_disputes: &[OpenDispute]) -> Result<(), Box<dyn Error>> {
+
+    let mut writer = csv_writer_with_header(File::create(path)?, &["client", "tx", "type", "amount", "opened_at", "sequence"])?;
+
+    for open_dispute in open_disputes {
+        writer.serialize(open_dispute)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
 
 /// A very lightweight main function:
 /// The spec doesn't require specific error behavior,
 /// so errors are just directly returned as soon as they're encountered.
 fn main() -> Result<(), Box<dyn Error>>{
 
-    let args: Vec<String> = std::env::args().collect();
+    let options = Options::parse(std::env::args().skip(1))
+        .map_err(|e| format!("{}\n{}", e, USAGE))?;
+
+    let mut engine = match options.load_snapshot_path {
+        Some(ref path) => Engine::load_snapshot(File::open(path)?, options.engine_config)?,
+        None => Engine::with_config(options.engine_config),
+    };
+
+    //the journal's transactions (since the snapshot) are replayed first
+    let mut journal = match options.journal_path {
+        Some(ref path) => Some(Journal::open(path, &mut engine)?),
+        None => None,
+    };
+
+    //each request is answered as soon as it's applied, so each journal entry is synced first
+    if let Some(ref address) = options.serve_address {
+
+        if options.http {
+            return serve_http(address, engine, journal);
+        }
+
+        let server = Server::bind(address, engine, journal)?;
+        eprintln!("listening on {}", server.local_addr()?);
+        server.run()?;
+        return Ok(());
+    }
+
+    //nothing is reported until the whole input is processed, so journal entries don't need to be synced one at a time
+    if let Some(ref mut journal) = journal {
+        journal.set_group_size(JOURNAL_GROUP_SIZE);
+    }
+
+    let admin_actions_writer = match options.admin_actions_path {
+        Some(ref path) => Some(csv_writer_with_header(File::create(path)?, &["file", "line", "client", "tx", "type"])?),
+        None => None,
+    };
 
-    let csv = read_to_string(args.get(1).ok_or("Specify input path")?)?;
+    let mut reporter = Reporter::new(options.parse_policy, admin_actions_writer);
 
-    let records = parse_csv(csv)?;
+    //records are streamed from the input files and applied one at a time, or queued for worker threads.
+    // One pipeline processes every file, so its workers aren't restarted for each one.
+    let mut pipeline = match options.threads {
+        1 => None,
+        threads => Some(Pipeline::new(std::mem::take(&mut engine), threads, QUEUE_CAPACITY)),
+    };
 
-    let account_states = run(&records);
+    let mut ledger = match options.ledger_path {
+        Some(ref path) => Some(Ledger::new(BufWriter::new(File::create(path)?))?),
+        None => None,
+    };
 
+    let input_paths = expand_globs(&options.input_paths)?;
 
-    println!("client, available, held, total, locked");
+    //line numbers restart in each input file
+    if let (Some(AsOf::Line(_)), true) = (options.as_of, input_paths.len() > 1) {
+        return Err("--as-of-line requires a single input file".into());
+    }
+
+    let mut as_of_limit = options.as_of.map(AsOfLimit::new);
+
+    //the input files are processed in order, as one input
+    for path in input_paths {
+
+        if as_of_limit.as_ref().is_some_and(AsOfLimit::is_reached) {
+            break;
+        }
+
+        reporter.start_file(&path);
+
+        let file = File::open(&path).map_err(|e| format!("{}: {}", path, e))?;
+        let input_format = options.input_format.unwrap_or_else(|| InputFormat::from_path(&path));
+
+        //records after the cutoff time are skipped; with a point in time, the input stops there
+        let records = read_input(file, input_format)
+            .filter(|record| !matches!((record, options.cutoff), (Ok(record), Some(cutoff)) if record.is_after(cutoff)))
+            .take_while(|record| as_of_limit.as_mut().is_none_or(|limit| limit.includes(record)));
 
-    for account_state in account_states {
-        println!("{}", account_state);
+        for record in records {
+            match (record, pipeline.as_mut()) {
+                //each record is tagged with its file's index, for any rejection by a worker
+                (Ok(record), Some(pipeline)) => reporter.result(pipeline.send(reporter.file_index(), record))?,
+                (Ok(record), None) => {
+                    let result = apply(&mut engine, &record, journal.as_mut(), ledger.as_mut())?;
+                    if result.is_ok() {
+                        reporter.applied(&record)?;
+                    }
+                    reporter.result(result)?
+                },
+                (Err(e), _) => reporter.invalid_line(e)?,
+            }
+        }
     }
 
+    if let Some(pipeline) = pipeline {
+
+        let (finished, rejections) = pipeline.finish();
+        for (file_index, rejection) in rejections {
+            reporter.worker_rejection(file_index, rejection);
+        }
+        engine = finished;
+    }
+
+    //the input's transactions are durable before any of their results are reported
+    if let Some(ref mut journal) = journal {
+        journal.sync()?;
+    }
+
+    reporter.finish()?;
+
+    //rejected transactions are only reported if requested
+    if let Some(ref path) = options.rejections_path {
+        reporter.write(path)?;
+    }
+
+    if let Some(ref mut ledger) = ledger {
+        ledger.flush()?;
+    }
+
+    //disputes left open at the end of the input are only reported if requested
+    if let Some(ref path) = options.open_disputes_path {
+        write_open_disputes(path, &engine.open_disputes())?;
+    }
+
+    if let Some(ref path) = options.save_snapshot_path {
+
+        engine.save_snapshot_file(path)?;
+
+        //the journal's transactions are all in the snapshot now
+        if let Some(ref mut journal) = journal {
+            journal.truncate()?;
+        }
+    }
+
+    let output: Box<dyn Write> = match options.output_path {
+        Some(ref path) => Box::new(File::create(path)?),
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    write_output(BufWriter::new(output), &engine.snapshot(), options.output_format, &options.csv_options)?;
+
     Ok(())
 }
@@ -0,0 +1,330 @@
+//! Writes account states in the selected output format
+
+use std::error::Error;
+use std::io::Write;
+use std::str::FromStr;
+
+use csv::{QuoteStyle,WriterBuilder};
+
+use crate::account_state::{AccountState,OutputRecord};
+
+
+/// The output columns, in order
+const COLUMNS: [&str; 5] = ["client", "available", "held", "total", "locked"];
+
+/// An account state output format
+#[derive(Copy,Clone,PartialEq,Default,Debug)]
+pub enum OutputFormat {
+    /// CSV lines, after a header line
+    #[default]
+    Csv,
+    /// A JSON array of objects
+    Json,
+    /// JSON Lines: one JSON object per line
+    Jsonl,
+    /// A human-readable table, with aligned columns
+    Table,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    /// Parses `csv`, `json`, `jsonl`, or `table`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!("invalid output format: {}", s)),
+        }
+    }
+}
+
+/// The CSV output header line style
+#[derive(Copy,Clone,PartialEq,Default,Debug)]
+pub enum HeaderStyle {
+    /// Column names separated by the delimiter only, like the data lines: `client,available,held,total,locked`
+    #[default]
+    Plain,
+    /// Column names separated by the delimiter and a space (the original output format):
+    /// `client, available, held, total, locked`
+    Spaced,
+    /// No header line
+    None,
+}
+
+impl FromStr for HeaderStyle {
+    type Err = String;
+
+    /// Parses `plain`, `spaced`, or `none`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        match s {
+            "plain" => Ok(HeaderStyle::Plain),
+            "spaced" => Ok(HeaderStyle::Spaced),
+            "none" => Ok(HeaderStyle::None),
+            _ => Err(format!("invalid CSV header style: {}", s)),
+        }
+    }
+}
+
+/// Which CSV output fields are quoted
+#[derive(Copy,Clone,PartialEq,Default,Debug)]
+pub enum Quoting {
+    /// Only fields that need quotes (e.g. ones containing the delimiter)
+    #[default]
+    Necessary,
+    Always,
+    /// No fields: the output can be invalid CSV, if a field contains the delimiter
+    Never,
+    /// Fields that aren't numbers
+    NonNumeric,
+}
+
+impl FromStr for Quoting {
+    type Err = String;
+
+    /// Parses `necessary`, `always`, `never`, or `non-numeric`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        match s {
+            "necessary" => Ok(Quoting::Necessary),
+            "always" => Ok(Quoting::Always),
+            "never" => Ok(Quoting::Never),
+            "non-numeric" => Ok(Quoting::NonNumeric),
+            _ => Err(format!("invalid CSV quoting: {}", s)),
+        }
+    }
+}
+
+impl From<Quoting> for QuoteStyle {
+
+    fn from(quoting: Quoting) -> Self {
+        match quoting {
+            Quoting::Necessary => QuoteStyle::Necessary,
+            Quoting::Always => QuoteStyle::Always,
+            Quoting::Never => QuoteStyle::Never,
+            Quoting::NonNumeric => QuoteStyle::NonNumeric,
+        }
+    }
+}
+
+/// CSV output options
+#[derive(Copy,Clone,PartialEq,Debug)]
+pub struct CsvOptions {
+    pub header: HeaderStyle,
+    /// The field delimiter: a single ASCII character
+    pub delimiter: u8,
+    pub quoting: Quoting,
+}
+
+impl Default for CsvOptions {
+
+    fn default() -> Self {
+        Self { header: HeaderStyle::default(), delimiter: b',', quoting: Quoting::default() }
+    }
+}
+
+/// Writes account states (with the output CSV's fields, see `OutputRecord`) in the specified format
+/// (`csv_options` only applies to CSV)
+pub fn write_output<W: Write>(mut writer: W, account_states: &[AccountState], format: OutputFormat, csv_options: &CsvOptions)
+    -> Result<(), Box<dyn Error>> {
+
+    let records = account_states.iter().map(OutputRecord::from);
+
+    match format {
+        OutputFormat::Csv => write_csv(&mut writer, records, csv_options)?,
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut writer, &records.collect::<Vec<_>>())?;
+            writeln!(writer)?;
+        },
+        OutputFormat::Jsonl => {
+            for record in records {
+                serde_json::to_writer(&mut writer, &record)?;
+                writeln!(writer)?;
+            }
+        },
+        OutputFormat::Table => write_table(&mut writer, records)?,
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// A CSV writer for a report (e.g. rejections or the ledger), after writing its header line.
+/// The header is written explicitly, so it's present even if the report has no lines.
+pub fn csv_writer_with_header<W: Write>(writer: W, header: &[&str]) -> csv::Result<csv::Writer<W>> {
+
+    let mut csv_writer = WriterBuilder::new().has_headers(false).from_writer(writer);
+    csv_writer.write_record(header)?;
+    Ok(csv_writer)
+}
+
+/// Writes CSV lines, after the header line (if any)
+fn write_csv<W: Write, I: Iterator<Item = OutputRecord>>(writer: &mut W, records: I, csv_options: &CsvOptions) -> csv::Result<()> {
+
+    let mut csv_writer = WriterBuilder::new()
+        .has_headers(false)
+        .delimiter(csv_options.delimiter)
+        .quote_style(csv_options.quoting.into())
+        .from_writer(writer);
+
+    match csv_options.header {
+        HeaderStyle::Plain => csv_writer.write_record(COLUMNS)?,
+        //a leading space before each column name (after the first) is written as part of its field
+        HeaderStyle::Spaced => csv_writer.write_record(COLUMNS.iter().enumerate()
+            .map(|(index, column)| if index == 0 { column.to_string() } else { format!(" {}", column) }))?,
+        HeaderStyle::None => {},
+    }
+
+    for record in records {
+        csv_writer.serialize(record)?;
+    }
+
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Writes a table with a header row, and each column right-aligned to its widest value
+fn write_table<W: Write, I: Iterator<Item = OutputRecord>>(writer: &mut W, records: I) -> std::io::Result<()> {
+
+    let rows: Vec<[String; 5]> = records.map(|record| [
+        record.client.to_string(),
+        record.available.to_string(),
+        record.held.to_string(),
+        record.total.to_string(),
+        record.locked.to_string(),
+    ]).collect();
+
+    let mut widths = COLUMNS.map(str::len);
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.len());
+        }
+    }
+
+    let header = COLUMNS.map(str::to_string);
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row.iter().zip(&widths)
+            .map(|(value, &width)| format!("{:>width$}", value, width = width))
+            .collect();
+        writeln!(writer, "{}", cells.join("  "))?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::amount::Amount;
+    use crate::engine::ClientId;
+
+    fn output(account_states: &[AccountState], format: OutputFormat) -> String {
+
+        csv_output(account_states, format, CsvOptions::default())
+    }
+
+    fn csv_output(account_states: &[AccountState], format: OutputFormat, csv_options: CsvOptions) -> String {
+
+        let mut buffer = Vec::new();
+        write_output(&mut buffer, account_states, format, &csv_options).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn write_output_test() {
+
+        let account_states = vec![
+            AccountState{client_id: ClientId(1), available: Amount(1_5000), held: Amount(0_0000), locked: false},
+            AccountState{client_id: ClientId(12), available: Amount(-1000_0000), held: Amount(1010_2500), locked: true},
+        ];
+
+        assert_eq!(output(&account_states, OutputFormat::Csv),
+"client,available,held,total,locked
+1,1.5000,0.0000,1.5000,false
+12,-1000.0000,1010.2500,10.2500,true
+");
+
+        assert_eq!(output(&account_states, OutputFormat::Json),
+r#"[{"client":1,"available":"1.5000","held":"0.0000","total":"1.5000","locked":false},{"client":12,"available":"-1000.0000","held":"1010.2500","total":"10.2500","locked":true}]
+"#);
+
+        assert_eq!(output(&account_states, OutputFormat::Jsonl),
+r#"{"client":1,"available":"1.5000","held":"0.0000","total":"1.5000","locked":false}
+{"client":12,"available":"-1000.0000","held":"1010.2500","total":"10.2500","locked":true}
+"#);
+
+        assert_eq!(output(&account_states, OutputFormat::Table),
+"client   available       held    total  locked
+     1      1.5000     0.0000   1.5000   false
+    12  -1000.0000  1010.2500  10.2500    true
+");
+
+        //no accounts
+        assert_eq!(output(&[], OutputFormat::Csv), "client,available,held,total,locked\n");
+        assert_eq!(output(&[], OutputFormat::Json), "[]\n");
+        assert_eq!(output(&[], OutputFormat::Jsonl), "");
+        assert_eq!(output(&[], OutputFormat::Table), "client  available  held  total  locked\n");
+    }
+
+    #[test]
+    fn csv_options_test() {
+
+        let account_states = vec![
+            AccountState{client_id: ClientId(1), available: Amount(1_5000), held: Amount(0_0000), locked: false},
+        ];
+
+        //header styles
+        assert_eq!(csv_output(&account_states, OutputFormat::Csv, CsvOptions { header: HeaderStyle::Spaced, ..CsvOptions::default() }),
+            "client, available, held, total, locked\n1,1.5000,0.0000,1.5000,false\n");
+        assert_eq!(csv_output(&account_states, OutputFormat::Csv, CsvOptions { header: HeaderStyle::None, ..CsvOptions::default() }),
+            "1,1.5000,0.0000,1.5000,false\n");
+
+        //delimiter
+        assert_eq!(csv_output(&account_states, OutputFormat::Csv, CsvOptions { delimiter: b'\t', ..CsvOptions::default() }),
+            "client\tavailable\theld\ttotal\tlocked\n1\t1.5000\t0.0000\t1.5000\tfalse\n");
+        assert_eq!(csv_output(&account_states, OutputFormat::Csv,
+                CsvOptions { header: HeaderStyle::Spaced, delimiter: b';', ..CsvOptions::default() }),
+            "client; available; held; total; locked\n1;1.5000;0.0000;1.5000;false\n");
+
+        //quoting
+        assert_eq!(csv_output(&account_states, OutputFormat::Csv, CsvOptions { quoting: Quoting::Always, ..CsvOptions::default() }),
+            "\"client\",\"available\",\"held\",\"total\",\"locked\"\n\"1\",\"1.5000\",\"0.0000\",\"1.5000\",\"false\"\n");
+        assert_eq!(csv_output(&account_states, OutputFormat::Csv, CsvOptions { quoting: Quoting::NonNumeric, ..CsvOptions::default() }),
+            "\"client\",\"available\",\"held\",\"total\",\"locked\"\n1,1.5000,0.0000,1.5000,\"false\"\n");
+
+        //the default output round-trips through a CSV reader
+        {
+            let output = output(&account_states, OutputFormat::Csv);
+            let mut reader = csv::Reader::from_reader(output.as_bytes());
+
+            assert_eq!(reader.headers().unwrap(), &csv::StringRecord::from(COLUMNS.to_vec()));
+            let rows: Vec<_> = reader.records().map(Result::unwrap).collect();
+            assert_eq!(rows, vec![csv::StringRecord::from(vec!["1", "1.5000", "0.0000", "1.5000", "false"])]);
+        }
+
+        //other formats ignore the CSV options
+        assert_eq!(csv_output(&account_states, OutputFormat::Jsonl, CsvOptions { delimiter: b';', ..CsvOptions::default() }),
+            output(&account_states, OutputFormat::Jsonl));
+
+        assert_eq!("spaced".parse(), Ok(HeaderStyle::Spaced));
+        assert_eq!("non-numeric".parse(), Ok(Quoting::NonNumeric));
+        assert!("fancy".parse::<HeaderStyle>().is_err());
+        assert!("sometimes".parse::<Quoting>().is_err());
+    }
+
+    #[test]
+    fn output_format_test() {
+
+        assert_eq!("csv".parse(), Ok(OutputFormat::Csv));
+        assert_eq!("json".parse(), Ok(OutputFormat::Json));
+        assert_eq!("jsonl".parse(), Ok(OutputFormat::Jsonl));
+        assert_eq!("table".parse(), Ok(OutputFormat::Table));
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,208 @@
+//! Parallel transaction processing:
+//! client accounts are independent, so they're sharded across worker threads
+
+use std::sync::mpsc::{sync_channel,SyncSender};
+use std::thread::JoinHandle;
+
+use crate::engine::{ClientId,Engine,Moment,Rejection,Transaction};
+use crate::input::InputRecord;
+
+
+/// Which of `shards` shards a client account belongs to
+fn shard_of(client_id: ClientId, shards: usize) -> usize {
+    client_id.0 as usize % shards
+}
+
+/// An admitted transaction, queued for a worker: with its input sequence number, source, record, and when it happened
+type Job = (usize, usize, InputRecord, ClientId, Transaction, Moment);
+
+/// A finished worker's client accounts and rejections (with their input sequence numbers and sources)
+type WorkerResult = (Engine, Vec<(usize, usize, Rejection)>);
+
+/// A streaming multi-threaded transaction processor:
+/// records are admitted (i.e. checked across client accounts) as they're sent,
+/// then queued for the worker thread that owns their client account.
+/// Each client's transactions are applied in input order.
+///
+/// Worker queues are bounded: when a worker's queue is full, `send` blocks until it has room,
+/// so a fast reader can't get arbitrarily far ahead of the workers.
+pub struct Pipeline {
+    //admits records: its client accounts are moved to the workers
+    dispatcher: Engine,
+    //the next record's input sequence number
+    sequence: usize,
+    //each worker's queue
+    queues: Vec<SyncSender<Job>>,
+    //each worker's result, when its queue is closed
+    workers: Vec<JoinHandle<WorkerResult>>,
+}
+
+impl Pipeline {
+
+    /// Starts `threads` worker threads, applying transactions to `engine`'s client accounts,
+    /// with each worker queue holding up to `queue_capacity` transactions
+    pub fn new(mut engine: Engine, threads: usize, queue_capacity: usize) -> Self {
+
+        let threads = threads.max(1);
+
+        let (queues, workers) = engine.split_accounts(threads, |client_id| shard_of(client_id, threads))
+            .into_iter()
+            .map(|mut worker| {
+                let (queue, jobs) = sync_channel::<Job>(queue_capacity);
+
+                let handle = std::thread::spawn(move || {
+                    let rejections = jobs.into_iter()
+                        .filter_map(|(sequence, source, record, client_id, transaction, now)| {
+                            worker.apply_admitted(client_id, &transaction, now).err()
+                                .map(|reason| (sequence, source, Rejection::new(&record, reason)))
+                        })
+                        .collect();
+                    (worker, rejections)
+                });
+
+                (queue, handle)
+            })
+            .unzip();
+
+        Self { dispatcher: engine, sequence: 0, queues, workers }
+    }
+
+    /// Sends the next transaction record to its client account's worker.
+    /// `source` identifies the record's input (e.g. the index of its input file), and is returned with its rejection, if any.
+    /// A record that fails admission is never queued: its Rejection is returned immediately.
+    pub fn send(&mut self, source: usize, record: InputRecord) -> Result<(), Rejection> {
+
+        let sequence = self.sequence;
+        self.sequence += 1;
+
+        let (client_id, transaction, now) = self.dispatcher.admit(&record)?;
+
+        self.queues[shard_of(client_id, self.queues.len())]
+            .send((sequence, source, record, client_id, transaction, now))
+            .expect("pipeline worker stopped");
+
+        Ok(())
+    }
+
+    /// Waits for the workers to apply every queued transaction, then merges their client accounts.
+    /// Returns the merged engine, and the workers' rejections (with their records' sources), in input order.
+    pub fn finish(self) -> (Engine, Vec<(usize, Rejection)>) {
+
+        let Pipeline { mut dispatcher, queues, workers, .. } = self;
+
+        //closing the queues stops the workers, once they're empty
+        drop(queues);
+
+        let mut rejections = Vec::new();
+
+        for handle in workers {
+            let (worker, worker_rejections) = handle.join().expect("pipeline worker panicked");
+            dispatcher.merge_accounts(worker);
+            rejections.extend(worker_rejections);
+        }
+
+        rejections.sort_by_key(|&(sequence, _, _)| sequence);
+
+        (dispatcher, rejections.into_iter().map(|(_, source, rejection)| (source, rejection)).collect())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::amount::Amount;
+    use crate::engine::RejectionReason;
+    use crate::timestamp::Timestamp;
+
+    /// A deterministic mix of transaction records over several clients,
+    /// including rejected, duplicate, untimed, and out-of-order transactions
+    fn generate_records(count: u32) -> Vec<InputRecord> {
+
+        let types = ["deposit", "deposit", "withdrawal", "dispute", "resolve", "chargeback", "unlock", "freeze", "bogus"];
+
+        //a simple linear congruential generator
+        let mut state: u64 = 12345;
+        let mut next = move |bound: u64| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+
+        (0..count).map(|i| {
+            let r#type = types[next(types.len() as u64) as usize];
+            let client = next(7) as u16;
+            let amount = match r#type {
+                "deposit" | "withdrawal" => Some(Amount(next(100_0000) as i64)),
+                _ => None,
+            };
+            //disputes and duplicates refer to earlier transaction IDs
+            let tx = if amount.is_none() || next(10) == 0 { next(i as u64 + 1) as u32 } else { i };
+
+            //timestamps mostly increase, but can go back a little
+            let timestamp = if next(4) == 0 { None } else { Some(Timestamp(i as i64 * 10 - next(100) as i64)) };
+
+            InputRecord{r#type: r#type.to_string(), client, tx, amount, line: i as u64 + 2, timestamp}
+        }).collect()
+    }
+
+    #[test]
+    fn pipeline_test() {
+
+        let records = generate_records(5000);
+
+        let mut sequential = Engine::new();
+        let expected: Vec<_> = records.iter().filter_map(|record| sequential.apply(record).err()).collect();
+
+        //the records include out-of-order transactions
+        assert!(expected.iter().any(|rejection| rejection.reason == RejectionReason::OutOfOrder));
+
+        for (threads, queue_capacity) in [(1, 1), (2, 16), (3, 1), (8, 1024)] {
+
+            let mut pipeline = Pipeline::new(Engine::new(), threads, queue_capacity);
+
+            //each record's source is its index
+            let mut rejections = Vec::new();
+            for (source, record) in records.iter().cloned().enumerate() {
+                if let Err(rejection) = pipeline.send(source, record) {
+                    rejections.push(rejection);
+                }
+            }
+
+            let (engine, worker_rejections) = pipeline.finish();
+            for (source, rejection) in worker_rejections {
+                assert_eq!(rejection.line, records[source].line);
+                rejections.push(rejection);
+            }
+            rejections.sort_by_key(|rejection| rejection.line);
+
+            assert_eq!(rejections, expected);
+            assert_eq!(engine.snapshot(), sequential.snapshot());
+        }
+
+        //continuing from an engine's existing accounts
+        {
+            let (first, rest) = records.split_at(2500);
+
+            let mut engine = Engine::new();
+            for record in first {
+                let _ = engine.apply(record);
+            }
+
+            let mut pipeline = Pipeline::new(engine, 4, 64);
+            for record in rest.iter().cloned() {
+                let _ = pipeline.send(0, record);
+            }
+            let (engine, _) = pipeline.finish();
+
+            assert_eq!(engine.snapshot(), sequential.snapshot());
+        }
+
+        //no records
+        {
+            let (engine, rejections) = Pipeline::new(Engine::new(), 4, 1).finish();
+
+            assert_eq!(engine.snapshot(), vec![]);
+            assert_eq!(rejections, vec![]);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! A TCP line-protocol server: applies transactions from any number of connections to a shared engine.
+//!
+//! Each request is one line, answered with one line:
+//! * a transaction, as a CSV line with no header (`type,client,tx,amount`, optionally followed by `,timestamp`):
+//!   answered with `OK`, `REJECTED <reason>`, or `ERROR <description>` for an invalid line
+//! * `BALANCE <client>`: answered with the client's account state, as an output CSV line
+//!   (`client,available,held,total,locked`), or `ERROR <description>` if the account isn't open
+
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+
+use crate::engine::{ClientId,Engine,Rejection};
+use crate::input::{parse_line,InputRecord};
+use crate::journal::{Journal,JournalError};
+
+
+/// The state shared by every connection
+pub(crate) struct Shared {
+    pub(crate) engine: Engine,
+    //if present: each admitted transaction is journaled before it's applied
+    pub(crate) journal: Option<Journal>,
+}
+
+impl Shared {
+
+    /// Applies a transaction record (journaling it first, if there's a journal)
+    pub(crate) fn apply(&mut self, record: &InputRecord) -> Result<Result<(), Rejection>, JournalError> {
+
+        match self.journal {
+            Some(ref mut journal) => self.engine.apply_journaled(record, journal),
+            None => Ok(self.engine.apply(record)),
+        }
+    }
+}
+
+/// A server bound to a TCP address
+pub struct Server {
+    listener: TcpListener,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Server {
+
+    /// Binds to `address`, serving requests with `engine` (and journaling to `journal`, if present)
+    pub fn bind<A: ToSocketAddrs>(address: A, engine: Engine, journal: Option<Journal>) -> std::io::Result<Self> {
+
+        let listener = TcpListener::bind(address)?;
+
+        Ok(Self { listener, shared: Arc::new(Mutex::new(Shared { engine, journal })) })
+    }
+
+    /// The address the server is bound to (e.g. to find the port chosen for port 0)
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections, each handled on its own thread.
+    /// Only returns if accepting a connection fails.
+    pub fn run(&self) -> std::io::Result<()> {
+
+        for stream in self.listener.incoming() {
+
+            let stream = stream?;
+            let shared = Arc::clone(&self.shared);
+
+            //a connection error only ends that connection
+            std::thread::spawn(move || handle_connection(stream, &shared));
+        }
+
+        Ok(())
+    }
+}
+
+/// Answers each request line from a connection, until it's closed
+fn handle_connection(stream: TcpStream, shared: &Mutex<Shared>) -> std::io::Result<()> {
+
+    let reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+
+    //lines are numbered per connection, starting at 1
+    for (line, request) in (1..).zip(reader.lines()) {
+
+        writeln!(writer, "{}", respond(&request?, line, shared))?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// The response line for a request line
+fn respond(request: &str, line: u64, shared: &Mutex<Shared>) -> String {
+
+    let request = request.trim();
+
+    if let Some(client) = request.strip_prefix("BALANCE ") {
+
+        let client = client.trim();
+        let client_id = match client.parse() {
+            Ok(client) => ClientId(client),
+            Err(_) => return format!("ERROR invalid client ID: {}", client),
+        };
+
+        return match shared.lock().expect("engine lock poisoned").engine.account_state(client_id) {
+            Some(account_state) => account_state.to_string(),
+            None => format!("ERROR no open account for client {}", client),
+        };
+    }
+
+    let record = match parse_line(request, line) {
+        Ok(record) => record,
+        Err(e) => return format!("ERROR {}", e),
+    };
+
+    match shared.lock().expect("engine lock poisoned").apply(&record) {
+        Ok(Ok(())) => "OK".to_string(),
+        Ok(Err(rejection)) => format!("REJECTED {}", rejection.reason),
+        Err(e) => format!("ERROR {}", e),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Starts a server on a local port chosen by the OS, and returns its address
+    fn start_server() -> SocketAddr {
+
+        let server = Server::bind("127.0.0.1:0", Engine::new(), None).unwrap();
+        let address = server.local_addr().unwrap();
+
+        std::thread::spawn(move || server.run());
+        address
+    }
+
+    /// A connection that sends one request line at a time, and returns its response line
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+
+        fn connect(address: SocketAddr) -> Self {
+            let writer = TcpStream::connect(address).unwrap();
+            Self { reader: BufReader::new(writer.try_clone().unwrap()), writer }
+        }
+
+        fn request(&mut self, request: &str) -> String {
+            self.writer.write_all(format!("{}\n", request).as_bytes()).unwrap();
+
+            let mut response = String::new();
+            self.reader.read_line(&mut response).unwrap();
+            response.trim_end().to_string()
+        }
+    }
+
+    #[test]
+    fn server_test() {
+
+        let address = start_server();
+
+        let mut client_a = Client::connect(address);
+        let mut client_b = Client::connect(address);
+
+        //transactions
+        assert_eq!(client_a.request("deposit, 1, 1, 10.0"), "OK");
+        assert_eq!(client_b.request("deposit,2,2,5"), "OK");
+        assert_eq!(client_a.request("withdrawal, 1, 3, 2.5"), "OK");
+
+        //every connection shares the same engine
+        assert_eq!(client_b.request("dispute, 1, 1,"), "OK");
+        assert_eq!(client_a.request("BALANCE 1"), "1,-2.5000,10.0000,7.5000,false");
+        assert_eq!(client_a.request("BALANCE 2"), "2,5.0000,0.0000,5.0000,false");
+
+        //rejections
+        assert_eq!(client_a.request("withdrawal, 2, 4, 100.0"), "REJECTED InsufficientFunds");
+        assert_eq!(client_b.request("deposit, 3, 1, 1.0"), "REJECTED DuplicateTx");
+        assert_eq!(client_b.request("refund, 3, 5, 1.0"), "REJECTED UnknownType");
+
+        //invalid requests
+        assert_eq!(client_a.request("deposit, x, 6, 1.0"), "ERROR line 6: invalid client ID: deposit, x, 6, 1.0");
+        assert_eq!(client_a.request("BALANCE 3"), "ERROR no open account for client 3");
+        assert_eq!(client_a.request("BALANCE x"), "ERROR invalid client ID: x");
+
+        //a closed connection doesn't affect the others
+        drop(client_a);
+        assert_eq!(client_b.request("BALANCE 1"), "1,-2.5000,10.0000,7.5000,false");
+    }
+}
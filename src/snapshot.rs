@@ -0,0 +1,244 @@
+//! Persistent engine state snapshots:
+//! an engine's full state can be saved after processing, and loaded to continue processing later
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::{Engine,EngineConfig};
+
+
+/// The current snapshot format version: incremented for any incompatible format change
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The snapshot JSON format: the version, the config the engine state was reached with, then the engine state
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    config: EngineConfig,
+    engine: &'a Engine,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    //absent from snapshots saved before it was included
+    #[serde(default)]
+    config: Option<EngineConfig>,
+    engine: Engine,
+}
+
+/// Only the version: checked before the rest of the snapshot is parsed
+#[derive(Deserialize)]
+struct SnapshotVersion {
+    version: u32,
+}
+
+/// A snapshot that couldn't be saved or loaded
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    /// The snapshot isn't valid JSON, or doesn't describe a valid engine state
+    Format(serde_json::Error),
+    /// The snapshot was written in a format version this build can't read
+    UnsupportedVersion(u32),
+    /// The snapshot was saved with a different engine config than it's loaded with
+    ConfigMismatch { saved: EngineConfig, config: EngineConfig },
+}
+
+impl Display for SnapshotError {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot I/O error: {}", e),
+            SnapshotError::Format(e) => write!(f, "invalid snapshot: {}", e),
+            SnapshotError::UnsupportedVersion(version) =>
+                write!(f, "unsupported snapshot version: {} (expected {})", version, SNAPSHOT_VERSION),
+            SnapshotError::ConfigMismatch { saved, config } =>
+                write!(f, "snapshot was saved with {:?}, but loaded with {:?}", saved, config),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+
+    fn from(e: std::io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Format(e)
+    }
+}
+
+impl Engine {
+
+    /// Writes a snapshot of this engine's state:
+    /// every client account (balances, locked flag, and disputable transactions)
+    /// and every transaction ID seen so far, along with the engine's config.
+    pub fn save_snapshot<W: Write>(&self, mut writer: W) -> Result<(), SnapshotError> {
+
+        serde_json::to_writer(&mut writer, &SnapshotRef { version: SNAPSHOT_VERSION, config: self.config(), engine: self })?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Writes a snapshot to the file at `path`, replacing it atomically:
+    /// the snapshot is written to a temporary file, which is renamed once it's on the storage device.
+    /// When this returns, the new snapshot is durable, so a crash can't leave a partial (or lost) snapshot.
+    pub fn save_snapshot_file<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+
+        let path = path.as_ref();
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        self.save_snapshot(&mut writer)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+        std::fs::rename(&temp_path, path)?;
+
+        //the rename is only durable once the directory is synced
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+
+        Ok(())
+    }
+
+    /// Reads an engine's state from a snapshot, to continue processing with `config`.
+    /// Fails if the snapshot was saved with a different config.
+    pub fn load_snapshot<R: Read>(mut reader: R, config: EngineConfig) -> Result<Self, SnapshotError> {
+
+        let mut json = String::new();
+        reader.read_to_string(&mut json)?;
+
+        let SnapshotVersion { version } = serde_json::from_str(&json)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let Snapshot { config: saved, mut engine } = serde_json::from_str(&json)?;
+        if let Some(saved) = saved.filter(|&saved| saved != config) {
+            return Err(SnapshotError::ConfigMismatch { saved, config });
+        }
+        engine.set_config(config);
+
+        Ok(engine)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::amount::Amount;
+    use crate::input::InputRecord;
+
+    #[test]
+    fn snapshot_test() {
+
+        let records = vec![
+            InputRecord{r#type: "deposit".to_string(), client: 1, tx: 1, amount: Some(Amount(10_0000)), line: 2, timestamp: None},
+            InputRecord{r#type: "deposit".to_string(), client: 2, tx: 2, amount: Some(Amount(5_0000)), line: 3, timestamp: None},
+            InputRecord{r#type: "withdrawal".to_string(), client: 1, tx: 3, amount: Some(Amount(2_5000)), line: 4, timestamp: None},
+            InputRecord{r#type: "dispute".to_string(), client: 1, tx: 1, amount: None, line: 5, timestamp: None},
+            InputRecord{r#type: "dispute".to_string(), client: 2, tx: 2, amount: None, line: 6, timestamp: None},
+            InputRecord{r#type: "chargeback".to_string(), client: 2, tx: 2, amount: None, line: 7, timestamp: None},
+            //after the snapshot:
+            InputRecord{r#type: "resolve".to_string(), client: 1, tx: 1, amount: None, line: 2, timestamp: None},
+            InputRecord{r#type: "dispute".to_string(), client: 1, tx: 3, amount: None, line: 3, timestamp: None},
+            InputRecord{r#type: "deposit".to_string(), client: 3, tx: 2, amount: Some(Amount(1_0000)), line: 4, timestamp: None},
+            InputRecord{r#type: "unlock".to_string(), client: 2, tx: 4, amount: None, line: 5, timestamp: None},
+            InputRecord{r#type: "dispute".to_string(), client: 1, tx: 1, amount: None, line: 6, timestamp: None},
+        ];
+        let (before, after) = records.split_at(6);
+
+        //success: processing continues from a snapshot exactly as if it hadn't been interrupted
+        for config in [EngineConfig::default(), EngineConfig { allow_redispute: false, ..EngineConfig::default() }] {
+
+            let mut uninterrupted = Engine::with_config(config);
+            let expected: Vec<_> = records.iter().map(|record| uninterrupted.apply(record)).collect();
+
+            let mut engine = Engine::with_config(config);
+            let mut results: Vec<_> = before.iter().map(|record| engine.apply(record)).collect();
+
+            let account_states = engine.snapshot();
+
+            let mut snapshot = Vec::new();
+            engine.save_snapshot(&mut snapshot).unwrap();
+
+            let mut engine = Engine::load_snapshot(snapshot.as_slice(), config).unwrap();
+            assert_eq!(engine.snapshot(), account_states);
+
+            results.extend(after.iter().map(|record| engine.apply(record)));
+
+            assert_eq!(results, expected);
+            assert_eq!(engine.snapshot(), uninterrupted.snapshot());
+        }
+
+        //success: empty engine
+        {
+            let mut snapshot = Vec::new();
+            Engine::new().save_snapshot(&mut snapshot).unwrap();
+
+            let engine = Engine::load_snapshot(snapshot.as_slice(), EngineConfig::default()).unwrap();
+            assert_eq!(engine.snapshot(), vec![]);
+        }
+
+        //success: saved to a file, replacing the previous snapshot
+        {
+            let path = std::env::temp_dir().join("rust2021_6_21_snapshot_file.json");
+            std::fs::write(&path, "previous").unwrap();
+
+            let mut engine = Engine::new();
+            for record in before {
+                let _ = engine.apply(record);
+            }
+            engine.save_snapshot_file(&path).unwrap();
+
+            let loaded = Engine::load_snapshot(std::fs::File::open(&path).unwrap(), EngineConfig::default()).unwrap();
+            assert_eq!(loaded.snapshot(), engine.snapshot());
+        }
+
+        //success: saved without a config
+        {
+            let snapshot = r#"{"version":1,"engine":{"accounts":{},"tx_ids":[]}}"#;
+            assert!(Engine::load_snapshot(snapshot.as_bytes(), EngineConfig { allow_redispute: false, ..EngineConfig::default() }).is_ok());
+        }
+
+        //failure: saved with a different config
+        {
+            let mut snapshot = Vec::new();
+            Engine::with_config(EngineConfig { allow_redispute: false, ..EngineConfig::default() }).save_snapshot(&mut snapshot).unwrap();
+
+            let result = Engine::load_snapshot(snapshot.as_slice(), EngineConfig::default());
+            assert!(matches!(result, Err(SnapshotError::ConfigMismatch { .. })));
+        }
+
+        //failure: unsupported version
+        {
+            let snapshot = r#"{"version":999,"engine":{}}"#;
+            let result = Engine::load_snapshot(snapshot.as_bytes(), EngineConfig::default());
+
+            assert!(matches!(result, Err(SnapshotError::UnsupportedVersion(999))));
+        }
+
+        //failure: not a snapshot
+        {
+            assert!(matches!(Engine::load_snapshot("".as_bytes(), EngineConfig::default()), Err(SnapshotError::Format(_))));
+            assert!(matches!(Engine::load_snapshot(r#"{"version":1}"#.as_bytes(), EngineConfig::default()), Err(SnapshotError::Format(_))));
+            assert!(matches!(Engine::load_snapshot(r#"{"version":1,"engine":{"accounts":[]}}"#.as_bytes(), EngineConfig::default()), Err(SnapshotError::Format(_))));
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Transaction timestamps
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use chrono::{DateTime, SecondsFormat};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error as DeError;
+
+
+/// The number of Timestamp units per second
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+/// The number of fractional second digits a Timestamp can represent
+const PRECISION: usize = 9;
+
+/// A transaction's point in time: an exact number of nanoseconds since the Unix epoch (1970-01-01T00:00:00Z)
+#[derive(Copy,Clone,Eq,PartialEq,Ord,PartialOrd,Hash,Debug)]
+pub struct Timestamp(pub i64);
+
+/// A Timestamp string that couldn't be parsed
+#[derive(PartialEq,Debug)]
+pub struct ParseTimestampError(String);
+
+impl Display for ParseTimestampError {
+
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid timestamp: {:?}", self.0)
+    }
+}
+
+impl Error for ParseTimestampError {}
+
+impl FromStr for Timestamp {
+    type Err = ParseTimestampError;
+
+    /// Parses an RFC 3339 date and time (e.g. "2021-06-21T12:30:00Z", or with an offset like "+02:00"),
+    /// or a number of seconds since the Unix epoch (e.g. "1624278600", or "1624278600.25")
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+
+        let err = || ParseTimestampError(s.to_string());
+
+        if s.contains('T') || s.contains('t') {
+            return DateTime::parse_from_rfc3339(s).ok()
+                .and_then(|date_time| date_time.timestamp_nanos_opt())
+                .map(Timestamp)
+                .ok_or_else(err);
+        }
+
+        //epoch seconds: exact, like amounts
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+
+        let (whole, fraction) = match digits.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (digits, ""),
+        };
+
+        if whole.is_empty() || fraction.len() > PRECISION {
+            return Err(err());
+        }
+        if !whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err(err());
+        }
+
+        let whole = whole.parse::<i64>().map_err(|_| err())?;
+
+        //right-pad the fraction to exactly PRECISION digits
+        let fraction = format!("{:0<width$}", fraction, width = PRECISION).parse::<i64>().map_err(|_| err())?;
+
+        let nanos = whole.checked_mul(NANOS_PER_SECOND)
+            .and_then(|nanos| nanos.checked_add(fraction))
+            .ok_or_else(err)?;
+
+        Ok(Timestamp(if negative { -nanos } else { nanos }))
+    }
+}
+
+impl Display for Timestamp {
+
+    /// Writes an RFC 3339 date and time in UTC, with as many fractional second digits as needed
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+
+        let date_time = DateTime::from_timestamp_nanos(self.0);
+        write!(f, "{}", date_time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(DeError::custom)
+    }
+}
+
+impl Serialize for Timestamp {
+
+    /// Serializes as RFC 3339 text, e.g. "2021-06-21T12:30:00Z"
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_str_test() {
+
+        //success: RFC 3339
+        assert_eq!("1970-01-01T00:00:00Z".parse(), Ok(Timestamp(0)));
+        assert_eq!("2021-06-21T12:30:00Z".parse(), Ok(Timestamp(1_624_278_600 * NANOS_PER_SECOND)));
+        assert_eq!("2021-06-21T14:30:00+02:00".parse(), Ok(Timestamp(1_624_278_600 * NANOS_PER_SECOND)));
+        assert_eq!("2021-06-21t12:30:00.25z".parse(), Ok(Timestamp(1_624_278_600 * NANOS_PER_SECOND + 250_000_000)));
+        assert_eq!("1969-12-31T23:59:59.999999999Z".parse(), Ok(Timestamp(-1)));
+
+        //success: epoch seconds
+        assert_eq!("0".parse(), Ok(Timestamp(0)));
+        assert_eq!("1624278600".parse(), Ok(Timestamp(1_624_278_600 * NANOS_PER_SECOND)));
+        assert_eq!("1624278600.25".parse(), Ok(Timestamp(1_624_278_600 * NANOS_PER_SECOND + 250_000_000)));
+        assert_eq!("-1.5".parse(), Ok(Timestamp(-1_500_000_000)));
+
+        //failure: invalid RFC 3339
+        assert!("2021-06-21T12:30:00".parse::<Timestamp>().is_err());
+        assert!("2021-06-21 12:30:00Z".parse::<Timestamp>().is_err());
+        assert!("2021-13-01T00:00:00Z".parse::<Timestamp>().is_err());
+
+        //failure: invalid epoch seconds
+        assert!("".parse::<Timestamp>().is_err());
+        assert!(".5".parse::<Timestamp>().is_err());
+        assert!("1.0000000001".parse::<Timestamp>().is_err());
+        assert!("1e9".parse::<Timestamp>().is_err());
+        assert!("yesterday".parse::<Timestamp>().is_err());
+
+        //failure: out of range
+        assert!("9223372037".parse::<Timestamp>().is_err());
+        assert!("2263-01-01T00:00:00Z".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn display_test() {
+
+        assert_eq!(Timestamp(0).to_string(), "1970-01-01T00:00:00Z");
+        assert_eq!(Timestamp(1_624_278_600 * NANOS_PER_SECOND + 250_000_000).to_string(), "2021-06-21T12:30:00.250Z");
+        assert_eq!(Timestamp(-1).to_string(), "1969-12-31T23:59:59.999999999Z");
+
+        //round trip
+        for timestamp in [Timestamp(0), Timestamp(123_456_789), Timestamp(-987_654_321_012)] {
+            assert_eq!(timestamp.to_string().parse(), Ok(timestamp));
+        }
+    }
+}
@@ -20,7 +20,7 @@ fn basic_test() -> Result<(), Box<dyn Error>> {
                 .output()?;
 
     let expected =
-"client, available, held, total, locked
+"client,available,held,total,locked
 1,1.5000,0.0000,1.5000,false
 2,2.0000,0.0000,2.0000,false
 ";
@@ -40,7 +40,7 @@ fn general_test() -> Result<(), Box<dyn Error>> {
                 .output()?;
 
     let expected =
-"client, available, held, total, locked
+"client,available,held,total,locked
 1,0.0000,0.0000,0.0000,false
 2,-2.0000,0.0000,-2.0000,true
 3,1.0000,2.0000,3.0000,false
@@ -50,4 +50,710 @@ fn general_test() -> Result<(), Box<dyn Error>> {
     assert_eq!(output_str, expected);
 
     Ok(())
-}
\ No newline at end of file
+}
+
+///test the rejected transactions report
+#[test]
+fn rejections_test() -> Result<(), Box<dyn Error>> {
+
+    let rejections_path = std::env::temp_dir().join("rust2021_6_21_rejections_test.csv");
+
+    let output = Command::cargo_bin(BIN_NAME)?
+                .arg("tests/general_test.csv")
+                .arg("--rejections")
+                .arg(&rejections_path)
+                .output()?;
+
+    let expected =
+"client,available,held,total,locked
+1,0.0000,0.0000,0.0000,false
+2,-2.0000,0.0000,-2.0000,true
+3,1.0000,2.0000,3.0000,false
+";
+
+    let output_str = std::str::from_utf8(&output.stdout)?;
+    assert_eq!(output_str, expected);
+
+    let expected_rejections =
+"file,line,client,tx,reason
+tests/general_test.csv,4,1,3,UnknownType
+tests/general_test.csv,5,3,6,AccountNotOpen
+";
+
+    let rejections = std::fs::read_to_string(&rejections_path)?;
+    assert_eq!(rejections, expected_rejections);
+
+    Ok(())
+}
+
+///test that deposits and withdrawals with zero or negative amounts are ignored
+#[test]
+fn non_positive_amounts_test() -> Result<(), Box<dyn Error>> {
+
+    let output = Command::cargo_bin(BIN_NAME)?
+                .arg("tests/non_positive_amounts_test.csv")
+                .output()?;
+
+    //a negative withdrawal doesn't create funds, and a negative deposit doesn't open an account
+    let expected =
+"client,available,held,total,locked
+2,10.0000,0.0000,10.0000,false
+";
+
+    let output_str = std::str::from_utf8(&output.stdout)?;
+    assert_eq!(output_str, expected);
+
+    Ok(())
+}
+
+///test the handling of invalid lines under each parse policy
+#[test]
+fn parse_policy_test() -> Result<(), Box<dyn Error>> {
+
+    let expected =
+"client,available,held,total,locked
+1,3.0000,0.0000,3.0000,false
+";
+
+    //lenient (the default): all invalid lines are skipped
+    {
+        let rejections_path = std::env::temp_dir().join("rust2021_6_21_parse_policy_test.csv");
+
+        let output = Command::cargo_bin(BIN_NAME)?
+                    .arg("tests/invalid_lines_test.csv")
+                    .arg("--rejections")
+                    .arg(&rejections_path)
+                    .output()?;
+
+        assert!(output.status.success());
+        assert_eq!(std::str::from_utf8(&output.stdout)?, expected);
+
+        let expected_rejections =
+"file,line,client,tx,reason
+tests/invalid_lines_test.csv,3,,,BadClientId
+tests/invalid_lines_test.csv,4,1,3,BadAmount
+tests/invalid_lines_test.csv,6,,,TruncatedLine
+tests/invalid_lines_test.csv,7,1,5,UnknownType
+";
+
+        let rejections = std::fs::read_to_string(&rejections_path)?;
+        assert_eq!(rejections, expected_rejections);
+
+        //skipped lines are only counted on stderr
+        assert_eq!(std::str::from_utf8(&output.stderr)?, "skipped 4 invalid lines\n");
+    }
+
+    //threshold: enough invalid lines are allowed
+    {
+        let output = Command::cargo_bin(BIN_NAME)?
+                    .args(["tests/invalid_lines_test.csv", "--parse-policy", "threshold:4"])
+                    .output()?;
+
+        assert!(output.status.success());
+        assert_eq!(std::str::from_utf8(&output.stdout)?, expected);
+    }
+
+    //threshold: too many invalid lines
+    {
+        let output = Command::cargo_bin(BIN_NAME)?
+                    .args(["tests/invalid_lines_test.csv", "--parse-policy", "threshold:3"])
+                    .output()?;
+
+        assert!(!output.status.success());
+        assert_eq!(std::str::from_utf8(&output.stdout)?, "");
+    }
+
+    //strict: the first invalid line aborts
+    {
+        let output = Command::cargo_bin(BIN_NAME)?
+                    .args(["tests/invalid_lines_test.csv", "--parse-policy", "strict"])
+                    .output()?;
+
+        assert!(!output.status.success());
+        assert_eq!(std::str::from_utf8(&output.stdout)?, "");
+        assert!(std::str::from_utf8(&output.stderr)?.contains("line 3: invalid client ID"));
+    }
+
+    Ok(())
+}
+
+///test the admin transaction types: unlock and freeze
+#[test]
+fn admin_test() -> Result<(), Box<dyn Error>> {
+
+    let rejections_path = std::env::temp_dir().join("rust2021_6_21_admin_test.csv");
+    let admin_actions_path = std::env::temp_dir().join("rust2021_6_21_admin_test_actions.csv");
+
+    let output = Command::cargo_bin(BIN_NAME)?
+                .arg("tests/admin_test.csv")
+                .arg("--rejections")
+                .arg(&rejections_path)
+                .arg("--admin-actions")
+                .arg(&admin_actions_path)
+                .output()?;
+
+    let expected =
+"client,available,held,total,locked
+1,2.0000,0.0000,2.0000,false
+2,5.0000,0.0000,5.0000,true
+";
+
+    let output_str = std::str::from_utf8(&output.stdout)?;
+    assert_eq!(output_str, expected);
+
+    let expected_rejections =
+"file,line,client,tx,reason
+tests/admin_test.csv,7,2,4,AccountLocked
+tests/admin_test.csv,10,1,7,NotLocked
+";
+
+    let rejections = std::fs::read_to_string(&rejections_path)?;
+    assert_eq!(rejections, expected_rejections);
+
+    //only applied admin actions are reported
+    let expected_admin_actions =
+"file,line,client,tx,type
+tests/admin_test.csv,6,2,3,freeze
+tests/admin_test.csv,8,1,5,unlock
+";
+
+    let admin_actions = std::fs::read_to_string(&admin_actions_path)?;
+    assert_eq!(admin_actions, expected_admin_actions);
+
+    Ok(())
+}
+
+#[test]
+fn threads_test() -> Result<(), Box<dyn Error>> {
+
+    //parallel processing gives the same output and rejections as sequential processing
+    for input_path in ["tests/basic_test.csv", "tests/general_test.csv", "tests/invalid_lines_test.csv", "tests/admin_test.csv"] {
+
+        let sequential_path = std::env::temp_dir().join("rust2021_6_21_threads_test_1.csv");
+        let parallel_path = std::env::temp_dir().join("rust2021_6_21_threads_test_4.csv");
+
+        let sequential = Command::cargo_bin(BIN_NAME)?
+                    .arg(input_path)
+                    .arg("--rejections")
+                    .arg(&sequential_path)
+                    .output()?;
+
+        let parallel = Command::cargo_bin(BIN_NAME)?
+                    .args([input_path, "--threads", "4"])
+                    .arg("--rejections")
+                    .arg(&parallel_path)
+                    .output()?;
+
+        assert!(parallel.status.success());
+        assert_eq!(parallel.stdout, sequential.stdout);
+        assert_eq!(std::fs::read_to_string(&parallel_path)?, std::fs::read_to_string(&sequential_path)?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn snapshot_test() -> Result<(), Box<dyn Error>> {
+
+    let snapshot_path = std::env::temp_dir().join("rust2021_6_21_snapshot_test.json");
+    let rejections_path = std::env::temp_dir().join("rust2021_6_21_snapshot_test.csv");
+
+    //day 1: save the engine state
+    let output = Command::cargo_bin(BIN_NAME)?
+                .arg("tests/snapshot_day1_test.csv")
+                .arg("--save-snapshot")
+                .arg(&snapshot_path)
+                .output()?;
+
+    let expected =
+"client,available,held,total,locked
+1,7.5000,0.0000,7.5000,false
+2,0.0000,5.0000,5.0000,false
+";
+
+    assert!(output.status.success());
+    assert_eq!(std::str::from_utf8(&output.stdout)?, expected);
+
+    //day 2: continue from day 1's state, including its disputes and transaction IDs
+    let output = Command::cargo_bin(BIN_NAME)?
+                .arg("tests/snapshot_day2_test.csv")
+                .arg("--load-snapshot")
+                .arg(&snapshot_path)
+                .arg("--rejections")
+                .arg(&rejections_path)
+                .output()?;
+
+    let expected =
+"client,available,held,total,locked
+1,-2.5000,10.0000,7.5000,false
+2,5.0000,0.0000,5.0000,false
+3,2.0000,0.0000,2.0000,false
+";
+
+    assert!(output.status.success());
+    assert_eq!(std::str::from_utf8(&output.stdout)?, expected);
+
+    let expected_rejections =
+"file,line,client,tx,reason
+tests/snapshot_day2_test.csv,4,1,2,DuplicateTx
+";
+
+    assert_eq!(std::fs::read_to_string(&rejections_path)?, expected_rejections);
+
+    //failure: missing snapshot
+    let output = Command::cargo_bin(BIN_NAME)?
+                .args(["tests/snapshot_day2_test.csv", "--load-snapshot", "tests/no_such_snapshot.json"])
+                .output()?;
+
+    assert!(!output.status.success());
+    assert_eq!(std::str::from_utf8(&output.stdout)?, "");
+
+    Ok(())
+}
+
+#[test]
+fn journal_test() -> Result<(), Box<dyn Error>> {
+
+    let journal_path = std::env::temp_dir().join("rust2021_6_21_journal_test.bin");
+    let snapshot_path = std::env::temp_dir().join("rust2021_6_21_journal_test.json");
+    let _ = std::fs::remove_file(&journal_path);
+
+    //day 1: journaled, but no snapshot is saved (e.g. the process stopped)
+    let output = Command::cargo_bin(BIN_NAME)?
+                .arg("tests/snapshot_day1_test.csv")
+                .arg("--journal")
+                .arg(&journal_path)
+                .output()?;
+
+    assert!(output.status.success());
+    let day1_len = std::fs::metadata(&journal_path)?.len();
+
+    //day 2: day 1's transactions are recovered from the journal
+    let output = Command::cargo_bin(BIN_NAME)?
+                .arg("tests/snapshot_day2_test.csv")
+                .arg("--journal")
+                .arg(&journal_path)
+                .arg("--save-snapshot")
+                .arg(&snapshot_path)
+                .output()?;
+
+    let expected =
+"client,available,held,total,locked
+1,-2.5000,10.0000,7.5000,false
+2,5.0000,0.0000,5.0000,false
+3,2.0000,0.0000,2.0000,false
+";
+
+    assert!(output.status.success());
+    assert_eq!(std::str::from_utf8(&output.stdout)?, expected);
+
+    //the saved snapshot includes the journal's transactions, so the journal is emptied (except for its config)
+    assert!(std::fs::metadata(&journal_path)?.len() < day1_len);
+
+    Ok(())
+}
+
+#[test]
+fn jsonl_test() -> Result<(), Box<dyn Error>> {
+
+    let rejections_path = std::env::temp_dir().join("rust2021_6_21_jsonl_test.csv");
+
+    //the same transactions as general_test.csv: selected by the file extension
+    let output = Command::cargo_bin(BIN_NAME)?
+                .arg("tests/general_test.jsonl")
+                .arg("--rejections")
+                .arg(&rejections_path)
+                .output()?;
+
+    let expected =
+"client,available,held,total,locked
+1,0.0000,0.0000,0.0000,false
+2,-2.0000,0.0000,-2.0000,true
+3,1.0000,2.0000,3.0000,false
+";
+
+    assert!(output.status.success());
+    assert_eq!(std::str::from_utf8(&output.stdout)?, expected);
+
+    //JSON Lines input has no header line
+    let expected_rejections =
+"file,line,client,tx,reason
+tests/general_test.jsonl,3,1,3,UnknownType
+tests/general_test.jsonl,4,3,6,AccountNotOpen
+";
+
+    assert_eq!(std::fs::read_to_string(&rejections_path)?, expected_rejections);
+
+    //failure: the format specified explicitly
+    let output = Command::cargo_bin(BIN_NAME)?
+                .args(["tests/general_test.jsonl", "--input-format", "csv", "--parse-policy", "strict"])
+                .output()?;
+
+    assert!(!output.status.success());
+
+    Ok(())
+}
+
+#[test]
+fn format_test() -> Result<(), Box<dyn Error>> {
+
+    let output = |format: &str| -> Result<String, Box<dyn Error>> {
+        let output = Command::cargo_bin(BIN_NAME)?
+                    .args(["tests/basic_test.csv", "--format", format])
+                    .output()?;
+        assert!(output.status.success());
+        Ok(String::from_utf8(output.stdout)?)
+    };
+
+    assert_eq!(output("csv")?,
+"client,available,held,total,locked
+1,1.5000,0.0000,1.5000,false
+2,2.0000,0.0000,2.0000,false
+");
+
+    assert_eq!(output("json")?,
+r#"[{"client":1,"available":"1.5000","held":"0.0000","total":"1.5000","locked":false},{"client":2,"available":"2.0000","held":"0.0000","total":"2.0000","locked":false}]
+"#);
+
+    assert_eq!(output("jsonl")?,
+r#"{"client":1,"available":"1.5000","held":"0.0000","total":"1.5000","locked":false}
+{"client":2,"available":"2.0000","held":"0.0000","total":"2.0000","locked":false}
+"#);
+
+    assert_eq!(output("table")?,
+"client  available    held   total  locked
+     1     1.5000  0.0000  1.5000   false
+     2     2.0000  0.0000  2.0000   false
+");
+
+    Ok(())
+}
+
+#[test]
+fn csv_options_test() -> Result<(), Box<dyn Error>> {
+
+    //CSV options
+    Command::cargo_bin(BIN_NAME)?
+        .args(["tests/basic_test.csv", "--csv-header", "spaced", "--csv-delimiter", "tab", "--csv-quote", "non-numeric"])
+        .assert()
+        .success()
+        .stdout(
+"\"client\"\t\" available\"\t\" held\"\t\" total\"\t\" locked\"
+1\t1.5000\t0.0000\t1.5000\t\"false\"
+2\t2.0000\t0.0000\t2.0000\t\"false\"
+");
+
+    //output to a file, instead of stdout
+    let output_path = std::env::temp_dir().join("rust2021_6_21_csv_options_test_output.csv");
+    let _ = std::fs::remove_file(&output_path);
+
+    Command::cargo_bin(BIN_NAME)?
+        .args(["tests/basic_test.csv", "--csv-header", "none", "--output"])
+        .arg(&output_path)
+        .assert()
+        .success()
+        .stdout("");
+
+    assert_eq!(std::fs::read_to_string(&output_path)?,
+"1,1.5000,0.0000,1.5000,false
+2,2.0000,0.0000,2.0000,false
+");
+
+    std::fs::remove_file(&output_path)?;
+
+    //failure: invalid delimiter
+    Command::cargo_bin(BIN_NAME)?
+        .args(["tests/basic_test.csv", "--csv-delimiter", "::"])
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+#[test]
+fn multiple_inputs_test() -> Result<(), Box<dyn Error>> {
+
+    let rejections_path = std::env::temp_dir().join("rust2021_6_21_multiple_inputs_test.csv");
+
+    let expected =
+"client,available,held,total,locked
+1,-2.5000,10.0000,7.5000,false
+2,5.0000,0.0000,5.0000,false
+3,2.0000,0.0000,2.0000,false
+";
+
+    //rejections are reported with their file, in input order
+    let expected_rejections =
+"file,line,client,tx,reason
+tests/snapshot_day2_test.csv,4,1,2,DuplicateTx
+";
+
+    //the files are processed in order as one input, like continuing from a snapshot; globs are expanded in alphabetical order
+    for args in [
+        vec!["tests/snapshot_day1_test.csv", "tests/snapshot_day2_test.csv"],
+        vec!["tests/snapshot_day?_test.csv"],
+        vec!["tests/snapshot_day1_test.csv", "tests/snapshot_day2_test.csv", "--threads", "3"],
+    ] {
+        let _ = std::fs::remove_file(&rejections_path);
+
+        let output = Command::cargo_bin(BIN_NAME)?
+                    .args(args)
+                    .arg("--rejections")
+                    .arg(&rejections_path)
+                    .output()?;
+
+        assert!(output.status.success());
+        assert_eq!(std::str::from_utf8(&output.stdout)?, expected);
+        assert_eq!(std::fs::read_to_string(&rejections_path)?, expected_rejections);
+    }
+
+    //worker rejections are reported with their file too
+    let mut reports = Vec::new();
+    for threads in ["1", "3"] {
+        let output = Command::cargo_bin(BIN_NAME)?
+                    .args(["tests/general_test.csv", "tests/basic_test.csv", "--threads", threads])
+                    .arg("--rejections")
+                    .arg(&rejections_path)
+                    .output()?;
+
+        assert!(output.status.success());
+        reports.push((output.stdout, std::fs::read_to_string(&rejections_path)?));
+    }
+    assert_eq!(reports[0], reports[1]);
+    assert!(reports[1].1.contains("\ntests/general_test.csv,5,3,6,AccountNotOpen\n"));
+
+    //failure: a glob with no matches
+    Command::cargo_bin(BIN_NAME)?
+        .args(["tests/basic_test.csv", "tests/no_such_*.csv"])
+        .assert()
+        .failure();
+
+    //failure: a missing file, after other files
+    let output = Command::cargo_bin(BIN_NAME)?
+                .args(["tests/basic_test.csv", "tests/no_such_file.csv"])
+                .output()?;
+
+    assert!(!output.status.success());
+    assert_eq!(std::str::from_utf8(&output.stdout)?, "");
+    assert!(std::str::from_utf8(&output.stderr)?.contains("tests/no_such_file.csv"));
+
+    Ok(())
+}
+
+#[test]
+fn ledger_test() -> Result<(), Box<dyn Error>> {
+
+    let ledger_path = std::env::temp_dir().join("rust2021_6_21_ledger_test.csv");
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("tests/general_test.csv")
+        .arg("--ledger")
+        .arg(&ledger_path)
+        .assert()
+        .success();
+
+    //a line for each applied transaction, in input order: rejected transactions have no effect, so they're omitted
+    let expected_ledger =
+"client,tx,type,available_delta,held_delta,available,held,total,locked
+1,1,deposit,1.0000,0.0000,1.0000,0.0000,1.0000,false
+2,4,deposit,10.0000,0.0000,10.0000,0.0000,10.0000,false
+2,5,withdrawal,-2.0000,0.0000,8.0000,0.0000,8.0000,false
+2,4,dispute,-10.0000,10.0000,-2.0000,10.0000,8.0000,false
+3,7,deposit,2.0000,0.0000,2.0000,0.0000,2.0000,false
+1,2,withdrawal,-1.0000,0.0000,0.0000,0.0000,0.0000,false
+3,8,withdrawal,-1.0000,0.0000,1.0000,0.0000,1.0000,false
+3,9,deposit,2.0000,0.0000,3.0000,0.0000,3.0000,false
+2,4,resolve,10.0000,-10.0000,8.0000,0.0000,8.0000,false
+2,4,dispute,-10.0000,10.0000,-2.0000,10.0000,8.0000,false
+3,9,dispute,-2.0000,2.0000,1.0000,2.0000,3.0000,false
+2,4,chargeback,0.0000,-10.0000,-2.0000,0.0000,-2.0000,true
+";
+
+    assert_eq!(std::fs::read_to_string(&ledger_path)?, expected_ledger);
+
+    std::fs::remove_file(&ledger_path)?;
+
+    Ok(())
+}
+
+#[test]
+fn as_of_test() -> Result<(), Box<dyn Error>> {
+
+    let output = |args: &[&str]| -> Result<String, Box<dyn Error>> {
+        let output = Command::cargo_bin(BIN_NAME)?.args(args).output()?;
+        assert!(output.status.success());
+        Ok(String::from_utf8(output.stdout)?)
+    };
+
+    //as of line 7: client 2's dispute is applied, but not its resolution
+    let expected =
+"client,available,held,total,locked
+1,1.0000,0.0000,1.0000,false
+2,-2.0000,10.0000,8.0000,false
+";
+
+    assert_eq!(output(&["tests/general_test.csv", "--as-of-line", "7"])?, expected);
+    assert_eq!(output(&["tests/general_test.csv", "--as-of-line", "7", "--threads", "2"])?, expected);
+
+    //as of transaction 9 (a deposit), including it
+    assert_eq!(output(&["tests/general_test.csv", "--as-of-tx", "9"])?,
+"client,available,held,total,locked
+1,0.0000,0.0000,0.0000,false
+2,-2.0000,10.0000,8.0000,false
+3,3.0000,0.0000,3.0000,false
+");
+
+    //as of a transaction in the first of several input files: the later files aren't processed
+    assert_eq!(output(&["tests/snapshot_day1_test.csv", "tests/snapshot_day2_test.csv", "--as-of-tx", "2"])?,
+"client,available,held,total,locked
+1,10.0000,0.0000,10.0000,false
+2,5.0000,0.0000,5.0000,false
+");
+
+    //failure: a line number with several input files
+    Command::cargo_bin(BIN_NAME)?
+        .args(["tests/snapshot_day1_test.csv", "tests/snapshot_day2_test.csv", "--as-of-line", "2"])
+        .assert()
+        .failure();
+
+    Ok(())
+}
+
+#[test]
+fn timestamps_test() -> Result<(), Box<dyn Error>> {
+
+    let rejections_path = std::env::temp_dir().join("rust2021_6_21_timestamps_test.csv");
+
+    let output = Command::cargo_bin(BIN_NAME)?
+                .arg("tests/timestamps_test.csv")
+                .arg("--rejections")
+                .arg(&rejections_path)
+                .output()?;
+
+    let expected =
+"client,available,held,total,locked
+1,-2.0000,10.0000,8.0000,false
+2,7.0000,0.0000,7.0000,false
+";
+
+    assert!(output.status.success());
+    assert_eq!(std::str::from_utf8(&output.stdout)?, expected);
+
+    //client 1's withdrawal is earlier than its previous one
+    let expected_rejections =
+"file,line,client,tx,reason
+tests/timestamps_test.csv,5,1,4,OutOfOrder
+";
+
+    assert_eq!(std::fs::read_to_string(&rejections_path)?, expected_rejections);
+
+    //as of a cutoff time: later records are skipped, so client 1's withdrawals are in order
+    let expected =
+"client,available,held,total,locked
+1,9.0000,0.0000,9.0000,false
+2,7.0000,0.0000,7.0000,false
+";
+
+    for threads in ["1", "2"] {
+        Command::cargo_bin(BIN_NAME)?
+            .args(["tests/timestamps_test.csv", "--cutoff", "2021-06-21T09:59:59Z", "--threads", threads])
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn time_limits_test() -> Result<(), Box<dyn Error>> {
+
+    //without time limits, every dispute and chargeback succeeds
+    let expected =
+"client,available,held,total,locked
+1,0.0000,10.0000,10.0000,false
+2,0.0000,0.0000,0.0000,true
+3,0.0000,0.0000,0.0000,true
+";
+
+    Command::cargo_bin(BIN_NAME)?
+        .arg("tests/time_limits_test.csv")
+        .assert()
+        .success()
+        .stdout(expected);
+
+    let rejections_path = std::env::temp_dir().join("rust2021_6_21_time_limits_test.csv");
+
+    let expected =
+"client,available,held,total,locked
+1,10.0000,0.0000,10.0000,false
+2,0.0000,5.0000,5.0000,false
+3,0.0000,0.0000,0.0000,true
+";
+
+    //client 1's deposit is disputed after 20 days, and client 2's dispute is charged back after 4 days
+    let expected_rejections =
+"file,line,client,tx,reason
+tests/time_limits_test.csv,5,1,1,DisputeWindowExpired
+tests/time_limits_test.csv,8,2,2,ChargebackDeadlinePassed
+";
+
+    for threads in ["1", "2"] {
+        Command::cargo_bin(BIN_NAME)?
+            .args(["tests/time_limits_test.csv", "--dispute-window", "7d", "--chargeback-deadline", "3d", "--threads", threads])
+            .arg("--rejections")
+            .arg(&rejections_path)
+            .assert()
+            .success()
+            .stdout(expected);
+
+        assert_eq!(std::fs::read_to_string(&rejections_path)?, expected_rejections);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn open_disputes_test() -> Result<(), Box<dyn Error>> {
+
+    let open_disputes_path = std::env::temp_dir().join("rust2021_6_21_open_disputes_test.csv");
+
+    let expected =
+"client,available,held,total,locked
+1,-2.5000,12.5000,10.0000,false
+2,0.0000,5.0000,5.0000,false
+3,1.0000,0.0000,1.0000,false
+";
+
+    //client 3's dispute is resolved: the others are still open at the end of the input
+    let expected_open_disputes =
+"client,tx,type,amount,opened_at,sequence
+1,1,deposit,10.0000,2021-06-21T12:00:00Z,8
+1,3,withdrawal,2.5000,2021-06-21T11:00:00Z,5
+2,2,deposit,5.0000,,6
+";
+
+    for threads in ["1", "2"] {
+        Command::cargo_bin(BIN_NAME)?
+            .args(["tests/open_disputes_test.csv", "--threads", threads])
+            .arg("--open-disputes")
+            .arg(&open_disputes_path)
+            .assert()
+            .success()
+            .stdout(expected);
+
+        assert_eq!(std::fs::read_to_string(&open_disputes_path)?, expected_open_disputes);
+    }
+
+    //no open disputes: just the header
+    Command::cargo_bin(BIN_NAME)?
+        .arg("tests/basic_test.csv")
+        .arg("--open-disputes")
+        .arg(&open_disputes_path)
+        .assert()
+        .success();
+
+    assert_eq!(std::fs::read_to_string(&open_disputes_path)?, "client,tx,type,amount,opened_at,sequence\n");
+
+    Ok(())
+}